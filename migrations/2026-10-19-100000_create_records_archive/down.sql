@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+insert into records (id, member_id, systolic, diastolic, bmp, record_at, created_at, updated_at)
+select id, member_id, systolic, diastolic, bmp, record_at, created_at, updated_at
+from records_archive;
+
+drop index idx_records_member_id_record_at;
+create index idx_records_member_id on records (member_id);
+
+drop table records_archive;
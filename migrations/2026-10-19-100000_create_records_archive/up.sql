@@ -0,0 +1,29 @@
+-- Your SQL goes here
+CREATE TABLE records_archive
+(
+    id          UUID PRIMARY KEY,
+    member_id   UUID                     NOT NULL,
+    systolic    INT                      NOT NULL,
+    diastolic   INT                      NOT NULL,
+    bmp         INT                      NOT NULL,
+    record_at   TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at  TIMESTAMP WITH TIME ZONE NOT NULL,
+    updated_at  TIMESTAMP WITH TIME ZONE NOT NULL,
+    archived_at TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp
+);
+
+create index idx_records_archive_member_id_record_at on records_archive (member_id, record_at);
+
+drop index idx_records_member_id;
+create index idx_records_member_id_record_at on records (member_id, record_at);
+
+comment on table records_archive is '归档记录表';
+comment on column records_archive.id is '编号';
+comment on column records_archive.member_id is '成员编号';
+comment on column records_archive.systolic is '收缩压';
+comment on column records_archive.diastolic is '舒张压';
+comment on column records_archive.bmp is '心率';
+comment on column records_archive.record_at is '记录时间';
+comment on column records_archive.created_at is '创建时间';
+comment on column records_archive.updated_at is '更新时间';
+comment on column records_archive.archived_at is '归档时间';
@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+drop index idx_members_search_vector;
+drop index idx_records_search_vector;
+
+alter table members drop column search_vector;
+alter table records_archive drop column note;
+alter table records drop column search_vector;
+alter table records drop column note;
+
+drop function bp_search_query(text, text);
+drop function bp_search_vector(text, text);
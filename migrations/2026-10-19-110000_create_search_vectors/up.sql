@@ -0,0 +1,23 @@
+-- Your SQL goes here
+create or replace function bp_search_vector(config text, document text) returns tsvector as
+$$
+select to_tsvector(config::regconfig, coalesce(document, ''))
+$$ language sql stable;
+
+create or replace function bp_search_query(config text, query text) returns tsquery as
+$$
+select plainto_tsquery(config::regconfig, query)
+$$ language sql stable;
+
+alter table records add column note VARCHAR;
+alter table records add column search_vector TSVECTOR NOT NULL default ''::tsvector;
+alter table records_archive add column note VARCHAR;
+alter table members add column search_vector TSVECTOR NOT NULL default ''::tsvector;
+
+create index idx_records_search_vector on records using gin (search_vector);
+create index idx_members_search_vector on members using gin (search_vector);
+
+comment on column records.note is '备注';
+comment on column records.search_vector is '全文检索向量';
+comment on column records_archive.note is '备注';
+comment on column members.search_vector is '全文检索向量';
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+drop table user_quotas;
+drop index idx_quota_plans_is_default;
+drop index idx_quota_plans_name;
+drop table quota_plans;
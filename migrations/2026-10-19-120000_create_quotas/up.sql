@@ -0,0 +1,37 @@
+-- Your SQL goes here
+CREATE TABLE quota_plans
+(
+    id           UUID PRIMARY KEY                  default uuid_generate_v4(),
+    name         VARCHAR                  NOT NULL,
+    member_limit INT                      NOT NULL,
+    is_default   BOOLEAN                  NOT NULL default false,
+    created_at   TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp,
+    updated_at   TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp
+);
+
+create unique index idx_quota_plans_name on quota_plans (name);
+create unique index idx_quota_plans_is_default on quota_plans (is_default) where is_default;
+
+comment on table quota_plans is '配额套餐表';
+comment on column quota_plans.id is '编号';
+comment on column quota_plans.name is '名称';
+comment on column quota_plans.member_limit is '成员上限';
+comment on column quota_plans.is_default is '是否默认套餐';
+comment on column quota_plans.created_at is '创建时间';
+comment on column quota_plans.updated_at is '更新时间';
+
+CREATE TABLE user_quotas
+(
+    user_id      UUID PRIMARY KEY,
+    plan_id      UUID,
+    member_limit INT,
+    created_at   TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp,
+    updated_at   TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp
+);
+
+comment on table user_quotas is '用户配额表';
+comment on column user_quotas.user_id is '用户编号';
+comment on column user_quotas.plan_id is '套餐编号';
+comment on column user_quotas.member_limit is '成员上限（覆盖套餐）';
+comment on column user_quotas.created_at is '创建时间';
+comment on column user_quotas.updated_at is '更新时间';
@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+alter table members drop column smoking;
+alter table members drop column ckd;
+alter table members drop column diabetes;
+alter table members drop column weight;
+alter table members drop column height;
+alter table members drop column sex;
+alter table members drop column birth_date;
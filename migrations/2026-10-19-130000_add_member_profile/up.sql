@@ -0,0 +1,16 @@
+-- Your SQL goes here
+alter table members add column birth_date DATE;
+alter table members add column sex VARCHAR;
+alter table members add column height INT;
+alter table members add column weight REAL;
+alter table members add column diabetes BOOLEAN NOT NULL default false;
+alter table members add column ckd BOOLEAN NOT NULL default false;
+alter table members add column smoking VARCHAR;
+
+comment on column members.birth_date is '出生日期';
+comment on column members.sex is '性别（male/female）';
+comment on column members.height is '身高（厘米）';
+comment on column members.weight is '体重（千克）';
+comment on column members.diabetes is '是否患有糖尿病';
+comment on column members.ckd is '是否患有慢性肾病';
+comment on column members.smoking is '吸烟状况（never/former/current）';
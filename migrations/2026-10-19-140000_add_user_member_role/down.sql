@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+drop index idx_user_member_member_id;
+
+alter table user_member drop column role;
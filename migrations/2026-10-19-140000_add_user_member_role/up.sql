@@ -0,0 +1,6 @@
+-- Your SQL goes here
+alter table user_member add column role VARCHAR NOT NULL default 'owner';
+
+create index idx_user_member_member_id on user_member (member_id);
+
+comment on column user_member.role is '角色（owner/editor/viewer）';
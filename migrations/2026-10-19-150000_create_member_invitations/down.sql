@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+drop index idx_member_invitations_member_id;
+drop index idx_member_invitations_code_hash;
+drop table member_invitations;
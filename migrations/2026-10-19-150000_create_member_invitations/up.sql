@@ -0,0 +1,31 @@
+-- Your SQL goes here
+CREATE TABLE member_invitations
+(
+    id          UUID PRIMARY KEY                  default uuid_generate_v4(),
+    member_id   UUID                     NOT NULL,
+    inviter_id  UUID                     NOT NULL,
+    code_hash   VARCHAR                  NOT NULL,
+    role        VARCHAR                  NOT NULL,
+    expires_at  TIMESTAMP WITH TIME ZONE NOT NULL,
+    accepted_by UUID,
+    accepted_at TIMESTAMP WITH TIME ZONE,
+    revoked_at  TIMESTAMP WITH TIME ZONE,
+    created_at  TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp,
+    updated_at  TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp
+);
+
+create unique index idx_member_invitations_code_hash on member_invitations (code_hash);
+create index idx_member_invitations_member_id on member_invitations (member_id);
+
+comment on table member_invitations is '成员邀请表';
+comment on column member_invitations.id is '编号';
+comment on column member_invitations.member_id is '成员编号';
+comment on column member_invitations.inviter_id is '邀请人编号';
+comment on column member_invitations.code_hash is '邀请码哈希';
+comment on column member_invitations.role is '角色（editor/viewer）';
+comment on column member_invitations.expires_at is '过期时间';
+comment on column member_invitations.accepted_by is '接受人编号';
+comment on column member_invitations.accepted_at is '接受时间';
+comment on column member_invitations.revoked_at is '撤销时间';
+comment on column member_invitations.created_at is '创建时间';
+comment on column member_invitations.updated_at is '更新时间';
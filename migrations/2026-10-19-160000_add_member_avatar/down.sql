@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+alter table members drop column avatar_url;
+alter table members drop column avatar_key;
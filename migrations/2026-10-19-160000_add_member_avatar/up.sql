@@ -0,0 +1,6 @@
+-- Your SQL goes here
+alter table members add column avatar_key VARCHAR;
+alter table members add column avatar_url VARCHAR;
+
+comment on column members.avatar_key is '头像存储键';
+comment on column members.avatar_url is '头像地址';
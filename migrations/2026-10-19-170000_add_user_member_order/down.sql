@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+alter table user_member drop column pinned;
+alter table user_member drop column sort_order;
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+alter table user_member add column sort_order INT;
+alter table user_member add column pinned BOOLEAN NOT NULL default false;
+
+comment on column user_member.sort_order is '排序（越小越靠前，为空时按最近更新排序）';
+comment on column user_member.pinned is '是否置顶';
@@ -0,0 +1,28 @@
+-- Your SQL goes here
+CREATE TABLE refresh_tokens
+(
+    id          UUID PRIMARY KEY                  default uuid_generate_v4(),
+    user_id     UUID                     NOT NULL,
+    family_id   UUID                     NOT NULL,
+    token_hash  VARCHAR                  NOT NULL,
+    expires_at  TIMESTAMP WITH TIME ZONE NOT NULL,
+    replaced_by UUID,
+    revoked_at  TIMESTAMP WITH TIME ZONE,
+    created_at  TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp,
+    updated_at  TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp
+);
+
+create unique index idx_refresh_tokens_token_hash on refresh_tokens (token_hash);
+create index idx_refresh_tokens_user_id on refresh_tokens (user_id);
+create index idx_refresh_tokens_family_id on refresh_tokens (family_id);
+
+comment on table refresh_tokens is '刷新令牌表';
+comment on column refresh_tokens.id is '编号';
+comment on column refresh_tokens.user_id is '用户编号';
+comment on column refresh_tokens.family_id is '令牌族编号（同一次登录轮换出的令牌）';
+comment on column refresh_tokens.token_hash is '令牌哈希';
+comment on column refresh_tokens.expires_at is '过期时间';
+comment on column refresh_tokens.replaced_by is '轮换后的令牌编号';
+comment on column refresh_tokens.revoked_at is '撤销时间';
+comment on column refresh_tokens.created_at is '创建时间';
+comment on column refresh_tokens.updated_at is '更新时间';
@@ -0,0 +1,20 @@
+-- Your SQL goes here
+CREATE TABLE revoked_tokens
+(
+    jti        UUID PRIMARY KEY,
+    user_id    UUID                     NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp
+);
+
+create index idx_revoked_tokens_expires_at on revoked_tokens (expires_at);
+
+comment on table revoked_tokens is '已撤销访问令牌表';
+comment on column revoked_tokens.jti is '令牌编号';
+comment on column revoked_tokens.user_id is '用户编号';
+comment on column revoked_tokens.expires_at is '令牌过期时间';
+comment on column revoked_tokens.created_at is '创建时间';
+
+alter table users add column token_generation INT NOT NULL default 0;
+
+comment on column users.token_generation is '令牌代数（递增后旧令牌全部失效）';
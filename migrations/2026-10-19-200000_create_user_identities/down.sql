@@ -0,0 +1,18 @@
+-- This file should undo anything in `up.sql`
+update users set session_key = '' where session_key is null;
+alter table users alter column session_key set not null;
+alter table users add column openid VARCHAR;
+
+update users
+set openid = user_identities.subject
+from user_identities
+where user_identities.user_id = users.id
+  and user_identities.provider = 'wechat';
+
+update users set openid = id::varchar where openid is null;
+alter table users alter column openid set not null;
+create unique index idx_users_openid on users (openid);
+
+comment on column users.openid is '微信编号';
+
+DROP TABLE user_identities;
@@ -0,0 +1,31 @@
+-- Your SQL goes here
+CREATE TABLE user_identities
+(
+    id         UUID PRIMARY KEY                  default uuid_generate_v4(),
+    user_id    UUID                     NOT NULL,
+    provider   VARCHAR                  NOT NULL,
+    subject    VARCHAR                  NOT NULL,
+    credential VARCHAR,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp
+);
+
+create unique index idx_user_identities_provider_subject on user_identities (provider, subject);
+create index idx_user_identities_user_id on user_identities (user_id);
+
+comment on table user_identities is '用户身份表';
+comment on column user_identities.id is '编号';
+comment on column user_identities.user_id is '用户编号';
+comment on column user_identities.provider is '身份提供方（wechat/password）';
+comment on column user_identities.subject is '外部编号（微信 openid 或用户名）';
+comment on column user_identities.credential is '凭据（密码哈希）';
+comment on column user_identities.created_at is '创建时间';
+comment on column user_identities.updated_at is '更新时间';
+
+insert into user_identities (user_id, provider, subject, created_at, updated_at)
+select id, 'wechat', openid, created_at, updated_at
+from users;
+
+drop index idx_users_openid;
+alter table users drop column openid;
+alter table users alter column session_key drop not null;
@@ -0,0 +1,24 @@
+-- Your SQL goes here
+CREATE TABLE sms_codes
+(
+    id          UUID PRIMARY KEY                  default uuid_generate_v4(),
+    phone       VARCHAR                  NOT NULL,
+    code_hash   VARCHAR                  NOT NULL,
+    expires_at  TIMESTAMP WITH TIME ZONE NOT NULL,
+    attempts    INT                      NOT NULL default 0,
+    consumed_at TIMESTAMP WITH TIME ZONE,
+    created_at  TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp,
+    updated_at  TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp
+);
+
+create index idx_sms_codes_phone on sms_codes (phone);
+
+comment on table sms_codes is '短信验证码表';
+comment on column sms_codes.id is '编号';
+comment on column sms_codes.phone is '手机号';
+comment on column sms_codes.code_hash is '验证码哈希';
+comment on column sms_codes.expires_at is '过期时间';
+comment on column sms_codes.attempts is '验证失败次数';
+comment on column sms_codes.consumed_at is '使用时间';
+comment on column sms_codes.created_at is '创建时间';
+comment on column sms_codes.updated_at is '更新时间';
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+alter table users drop column phone;
+alter table users drop column avatar_url;
+alter table users drop column nickname;
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+alter table users add column nickname VARCHAR;
+alter table users add column avatar_url VARCHAR;
+alter table users add column phone VARCHAR;
+
+comment on column users.nickname is '昵称';
+comment on column users.avatar_url is '头像地址';
+comment on column users.phone is '手机号';
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+-- Sealed keys cannot be decrypted here; clients get a fresh one on next login.
+update users set session_key = null where session_key like 'enc:%';
+comment on column users.session_key is '微信密钥';
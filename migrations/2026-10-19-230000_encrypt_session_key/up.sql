@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- Existing plaintext keys stay plaintext until the application seals them on
+-- startup, since the encryption key is only available to the service. The
+-- server refuses to launch while that fails.
+comment on column users.session_key is '微信密钥（AES-GCM 加密）';
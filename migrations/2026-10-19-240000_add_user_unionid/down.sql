@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+drop index idx_users_unionid;
+alter table users drop column unionid;
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+alter table users add column unionid VARCHAR;
+
+create unique index idx_users_unionid on users (unionid);
+
+comment on column users.unionid is '微信开放平台 UnionID';
@@ -0,0 +1,38 @@
+-- Your SQL goes here
+CREATE TABLE account_deletions
+(
+    user_id         UUID PRIMARY KEY,
+    code_hash       VARCHAR                  NOT NULL,
+    code_expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    erase_at        TIMESTAMP WITH TIME ZONE,
+    created_at      TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp,
+    updated_at      TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp
+);
+
+create index idx_account_deletions_erase_at on account_deletions (erase_at);
+
+comment on table account_deletions is '账号注销申请表';
+comment on column account_deletions.user_id is '用户编号';
+comment on column account_deletions.code_hash is '确认码哈希';
+comment on column account_deletions.code_expires_at is '确认码过期时间';
+comment on column account_deletions.erase_at is '计划删除时间（确认后设置）';
+comment on column account_deletions.created_at is '创建时间';
+comment on column account_deletions.updated_at is '更新时间';
+
+CREATE TABLE account_erasures
+(
+    id           UUID PRIMARY KEY                  default uuid_generate_v4(),
+    user_id      UUID                     NOT NULL,
+    requested_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    member_num   INT                      NOT NULL,
+    record_num   INT                      NOT NULL,
+    created_at   TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp
+);
+
+comment on table account_erasures is '账号删除审计表';
+comment on column account_erasures.id is '编号';
+comment on column account_erasures.user_id is '已删除的用户编号';
+comment on column account_erasures.requested_at is '确认注销时间';
+comment on column account_erasures.member_num is '删除的成员数';
+comment on column account_erasures.record_num is '删除的记录数';
+comment on column account_erasures.created_at is '删除时间';
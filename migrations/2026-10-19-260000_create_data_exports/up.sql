@@ -0,0 +1,25 @@
+-- Your SQL goes here
+CREATE TABLE data_exports
+(
+    id          UUID PRIMARY KEY                  default uuid_generate_v4(),
+    user_id     UUID                     NOT NULL,
+    token_hash  VARCHAR                  NOT NULL,
+    status      VARCHAR                  NOT NULL default 'pending',
+    storage_key VARCHAR,
+    expires_at  TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at  TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp,
+    updated_at  TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp
+);
+
+create unique index idx_data_exports_token_hash on data_exports (token_hash);
+create index idx_data_exports_user_id on data_exports (user_id);
+
+comment on table data_exports is '个人数据导出表';
+comment on column data_exports.id is '编号';
+comment on column data_exports.user_id is '用户编号';
+comment on column data_exports.token_hash is '下载令牌哈希';
+comment on column data_exports.status is '状态（pending/ready/failed）';
+comment on column data_exports.storage_key is '导出文件存储键';
+comment on column data_exports.expires_at is '过期时间';
+comment on column data_exports.created_at is '创建时间';
+comment on column data_exports.updated_at is '更新时间';
@@ -0,0 +1,20 @@
+-- Your SQL goes here
+CREATE TABLE user_profiles
+(
+    user_id     UUID PRIMARY KEY,
+    timezone    VARCHAR                  NOT NULL,
+    language    VARCHAR                  NOT NULL default 'zh-CN',
+    bp_unit     VARCHAR                  NOT NULL default 'mmHg',
+    date_format VARCHAR                  NOT NULL default 'YYYY-MM-DD',
+    created_at  TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp,
+    updated_at  TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp
+);
+
+comment on table user_profiles is '用户偏好设置表';
+comment on column user_profiles.user_id is '用户编号';
+comment on column user_profiles.timezone is 'IANA 时区';
+comment on column user_profiles.language is '语言';
+comment on column user_profiles.bp_unit is '血压单位（mmHg/kPa）';
+comment on column user_profiles.date_format is '日期格式（YYYY-MM-DD/DD/MM/YYYY/MM/DD/YYYY）';
+comment on column user_profiles.created_at is '创建时间';
+comment on column user_profiles.updated_at is '更新时间';
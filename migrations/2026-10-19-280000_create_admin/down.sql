@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE admin_audit_logs;
+DROP TABLE login_events;
+alter table users drop column disabled_at;
+alter table users drop column role;
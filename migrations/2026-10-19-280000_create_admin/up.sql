@@ -0,0 +1,47 @@
+-- Your SQL goes here
+alter table users add column role VARCHAR NOT NULL default 'user';
+alter table users add column disabled_at TIMESTAMP WITH TIME ZONE;
+
+comment on column users.role is '角色（user、admin）';
+comment on column users.disabled_at is '停用时间';
+
+CREATE TABLE login_events
+(
+    id         UUID PRIMARY KEY                  default uuid_generate_v4(),
+    user_id    UUID                     NOT NULL,
+    provider   VARCHAR                  NOT NULL,
+    ip         VARCHAR,
+    user_agent VARCHAR,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp
+);
+
+create index idx_login_events_user_id on login_events (user_id, created_at);
+
+comment on table login_events is '登录记录表';
+comment on column login_events.id is '编号';
+comment on column login_events.user_id is '用户编号';
+comment on column login_events.provider is '登录方式';
+comment on column login_events.ip is '客户端地址';
+comment on column login_events.user_agent is '客户端标识';
+comment on column login_events.created_at is '登录时间';
+
+CREATE TABLE admin_audit_logs
+(
+    id             UUID PRIMARY KEY                  default uuid_generate_v4(),
+    admin_id       UUID                     NOT NULL,
+    action         VARCHAR                  NOT NULL,
+    target_user_id UUID,
+    detail         JSONB                    NOT NULL default '{}',
+    created_at     TIMESTAMP WITH TIME ZONE NOT NULL default current_timestamp
+);
+
+create index idx_admin_audit_logs_admin_id on admin_audit_logs (admin_id, created_at);
+create index idx_admin_audit_logs_target_user_id on admin_audit_logs (target_user_id, created_at);
+
+comment on table admin_audit_logs is '管理操作审计表';
+comment on column admin_audit_logs.id is '编号';
+comment on column admin_audit_logs.admin_id is '管理员编号';
+comment on column admin_audit_logs.action is '操作';
+comment on column admin_audit_logs.target_user_id is '目标用户编号';
+comment on column admin_audit_logs.detail is '操作详情';
+comment on column admin_audit_logs.created_at is '操作时间';
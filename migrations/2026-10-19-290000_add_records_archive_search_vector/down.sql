@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+drop index idx_records_archive_search_vector;
+alter table records_archive drop column search_vector;
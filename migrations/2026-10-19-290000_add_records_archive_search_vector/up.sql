@@ -0,0 +1,6 @@
+-- Your SQL goes here
+alter table records_archive add column search_vector TSVECTOR NOT NULL default ''::tsvector;
+
+create index idx_records_archive_search_vector on records_archive using gin (search_vector);
+
+comment on column records_archive.search_vector is '全文检索向量';
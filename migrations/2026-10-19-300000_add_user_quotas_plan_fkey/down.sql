@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+alter table user_quotas drop constraint fk_user_quotas_plan_id;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+update user_quotas set plan_id = null where plan_id not in (select id from quota_plans);
+
+alter table user_quotas
+    add constraint fk_user_quotas_plan_id foreign key (plan_id) references quota_plans (id) on delete set null;
@@ -0,0 +1,138 @@
+use crate::db::BpRecordConn;
+use crate::db::admin::{AdminAuditLogs, AdminMember, AuditQuery, QuotaUpdate, UserSearch};
+use crate::db::login::LoginEvents;
+use crate::db::member::MergeReport;
+use crate::db::quota::MemberQuota;
+use crate::db::user::Users;
+use crate::error::api::ApiError;
+use crate::storage::Storage;
+use crate::util::json::Json;
+use crate::util::jwt::{Admin, Uid};
+use rocket::{State, routes};
+use serde::Deserialize;
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![
+        users,
+        user_members,
+        user_logins,
+        disable_user,
+        enable_user,
+        update_quota,
+        merge_members,
+        audit_logs
+    ]
+}
+
+#[derive(Deserialize)]
+struct ReasonPayload {
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+fn reason(payload: Option<Json<ReasonPayload>>) -> Option<String> {
+    payload.and_then(|payload| payload.into_inner().reason)
+}
+
+#[get("/users?<search..>")]
+async fn users(
+    conn: BpRecordConn,
+    admin: Admin,
+    search: UserSearch,
+) -> Result<Json<Vec<Users>>, ApiError> {
+    let user_list = admin.search_users(&conn, search).await?;
+    Ok(Json(user_list))
+}
+
+#[get("/users/<user_id>/members")]
+async fn user_members(
+    conn: BpRecordConn,
+    admin: Admin,
+    user_id: Uid,
+) -> Result<Json<Vec<AdminMember>>, ApiError> {
+    let member_list = admin.user_members(&conn, user_id.into()).await?;
+    Ok(Json(member_list))
+}
+
+#[get("/users/<user_id>/logins?<page>")]
+async fn user_logins(
+    conn: BpRecordConn,
+    admin: Admin,
+    user_id: Uid,
+    page: Option<i64>,
+) -> Result<Json<Vec<LoginEvents>>, ApiError> {
+    let event_list = admin.user_logins(&conn, user_id.into(), page).await?;
+    Ok(Json(event_list))
+}
+
+#[post("/users/<user_id>/disable", data = "<payload>")]
+async fn disable_user(
+    conn: BpRecordConn,
+    admin: Admin,
+    user_id: Uid,
+    payload: Option<Json<ReasonPayload>>,
+) -> Result<Json<Users>, ApiError> {
+    let user = admin
+        .disable_user(&conn, user_id.into(), reason(payload))
+        .await?;
+    Ok(Json(user))
+}
+
+#[post("/users/<user_id>/enable", data = "<payload>")]
+async fn enable_user(
+    conn: BpRecordConn,
+    admin: Admin,
+    user_id: Uid,
+    payload: Option<Json<ReasonPayload>>,
+) -> Result<Json<Users>, ApiError> {
+    let user = admin
+        .enable_user(&conn, user_id.into(), reason(payload))
+        .await?;
+    Ok(Json(user))
+}
+
+#[put("/users/<user_id>/quota", data = "<quota>")]
+async fn update_quota(
+    conn: BpRecordConn,
+    admin: Admin,
+    user_id: Uid,
+    quota: Json<QuotaUpdate>,
+) -> Result<Json<MemberQuota>, ApiError> {
+    let member_quota = admin
+        .update_quota(&conn, user_id.into(), quota.into_inner())
+        .await?;
+    Ok(Json(member_quota))
+}
+
+#[post("/users/<user_id>/members/<member_id>/merge/<source_id>")]
+async fn merge_members(
+    conn: BpRecordConn,
+    storage: &State<Box<dyn Storage>>,
+    admin: Admin,
+    user_id: Uid,
+    member_id: Uid,
+    source_id: Uid,
+) -> Result<Json<MergeReport>, ApiError> {
+    let (report, avatar_key) = admin
+        .merge_members(&conn, user_id.into(), member_id.into(), source_id.into())
+        .await?;
+    if let Some(key) = avatar_key
+        && let Err(err) = storage.delete(&key).await
+    {
+        warn!(
+            "failed to delete avatar {} of merged member: {:?}",
+            key, err
+        );
+    }
+    Ok(Json(report))
+}
+
+#[get("/audit?<query..>")]
+async fn audit_logs(
+    conn: BpRecordConn,
+    _admin: Admin,
+    query: AuditQuery,
+) -> Result<Json<Vec<AdminAuditLogs>>, ApiError> {
+    let audit_list = AdminAuditLogs::select(&conn, query).await?;
+    Ok(Json(audit_list))
+}
@@ -0,0 +1,58 @@
+use crate::db::BpRecordConn;
+use crate::db::member::{MemberRole, Members};
+use crate::db::record::Records;
+use crate::error::api::ApiError;
+use crate::model::fhir::{Bundle, SkippedResource, extract_records};
+use crate::util::json::Json;
+use crate::util::jwt::Uid;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![observations, import_observations]
+}
+
+#[derive(Serialize)]
+struct ImportReport {
+    imported: Vec<Records>,
+    skipped: Vec<SkippedResource>,
+}
+
+#[get("/Patient/<member_id>/Observation")]
+async fn observations(
+    conn: BpRecordConn,
+    user_id: Uid,
+    member_id: Uid,
+) -> Result<Json<Bundle>, ApiError> {
+    let user_member =
+        Members::check_user(&conn, user_id.into(), member_id.into(), MemberRole::Viewer).await?;
+    let member = Members::detail(&conn, user_member.member_id).await?;
+    let record_list = Records::get_full_history(&conn, member.id).await?;
+    Ok(Json(Bundle::observations(&member, &record_list)))
+}
+
+#[post("/Patient/<member_id>/Observation", data = "<payload>")]
+async fn import_observations(
+    conn: BpRecordConn,
+    user_id: Uid,
+    member_id: Uid,
+    payload: Json<Value>,
+) -> Result<Json<ImportReport>, ApiError> {
+    let user_member =
+        Members::check_user(&conn, user_id.into(), member_id.into(), MemberRole::Editor).await?;
+    let (extracted, mut skipped) = extract_records(payload.into_inner())?;
+    let (entries, new_records): (Vec<_>, Vec<_>) = extracted.into_iter().unzip();
+    let (imported, duplicates) =
+        Records::insert_batch(&conn, user_member.member_id, new_records).await?;
+    let duplicates = duplicates.into_iter().collect::<HashSet<_>>();
+    skipped.extend(
+        entries
+            .into_iter()
+            .enumerate()
+            .filter(|(position, _)| duplicates.contains(position))
+            .map(|(_, entry)| entry.skip("已存在相同的记录")),
+    );
+    skipped.sort_by_key(|skipped| skipped.index);
+    Ok(Json(ImportReport { imported, skipped }))
+}
@@ -0,0 +1,113 @@
+use crate::db::BpRecordConn;
+use crate::db::invitation::{InvitationCode, MemberInvitations, NewInvitation, NewTransfer};
+use crate::db::member::{MemberRole, Members, UserMember};
+use crate::error::api::ApiError;
+use crate::util::json::Json;
+use crate::util::jwt::Uid;
+use crate::util::rate_limit::RateLimiter;
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use std::env;
+use std::time::Duration;
+use uuid::Uuid;
+
+lazy_static! {
+    static ref ACCEPT_LIMITER: RateLimiter = {
+        let max = env::var("INVITATION_ACCEPT_LIMIT")
+            .unwrap_or_else(|_| "5".to_owned())
+            .parse::<usize>()
+            .unwrap();
+        let window = env::var("INVITATION_ACCEPT_WINDOW")
+            .unwrap_or_else(|_| "600".to_owned())
+            .parse::<u64>()
+            .unwrap();
+        RateLimiter::new(max, Duration::from_secs(window))
+    };
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![
+        add_invitation,
+        add_transfer,
+        invitations,
+        revoke_invitation,
+        accept_invitation
+    ]
+}
+
+#[derive(Deserialize)]
+struct AcceptPayload {
+    code: String,
+}
+
+#[post("/", data = "<new_invitation>")]
+async fn add_invitation(
+    conn: BpRecordConn,
+    user_id: Uid,
+    new_invitation: Json<NewInvitation>,
+) -> Result<Json<InvitationCode>, ApiError> {
+    if new_invitation.role == MemberRole::Owner {
+        return Err(ApiError::BadRequest(String::from(
+            "邀请角色只能是 editor 或 viewer",
+        )));
+    }
+    let user_id: Uuid = user_id.into();
+    Members::check_user(&conn, user_id, new_invitation.member_id, MemberRole::Owner).await?;
+    let invitation = MemberInvitations::insert(&conn, user_id, new_invitation.into_inner()).await?;
+    Ok(Json(invitation))
+}
+
+#[post("/transfer", data = "<new_transfer>")]
+async fn add_transfer(
+    conn: BpRecordConn,
+    user_id: Uid,
+    new_transfer: Json<NewTransfer>,
+) -> Result<Json<InvitationCode>, ApiError> {
+    let user_id: Uuid = user_id.into();
+    Members::check_user(&conn, user_id, new_transfer.member_id, MemberRole::Owner).await?;
+    let invitation =
+        MemberInvitations::insert(&conn, user_id, new_transfer.into_inner().into()).await?;
+    Ok(Json(invitation))
+}
+
+#[get("/?<member_id>")]
+async fn invitations(
+    conn: BpRecordConn,
+    user_id: Uid,
+    member_id: Uid,
+) -> Result<Json<Vec<MemberInvitations>>, ApiError> {
+    let user_member =
+        Members::check_user(&conn, user_id.into(), member_id.into(), MemberRole::Owner).await?;
+    let invitation_list = MemberInvitations::get_pending(&conn, user_member.member_id).await?;
+    Ok(Json(invitation_list))
+}
+
+#[delete("/<invitation_id>")]
+async fn revoke_invitation(
+    conn: BpRecordConn,
+    user_id: Uid,
+    invitation_id: Uid,
+) -> Result<Json<MemberInvitations>, ApiError> {
+    let invitation = MemberInvitations::detail(&conn, invitation_id.into()).await?;
+    Members::check_user(
+        &conn,
+        user_id.into(),
+        invitation.member_id,
+        MemberRole::Owner,
+    )
+    .await?;
+    let invitation = MemberInvitations::revoke(&conn, invitation.id).await?;
+    Ok(Json(invitation))
+}
+
+#[post("/accept", data = "<payload>")]
+async fn accept_invitation(
+    conn: BpRecordConn,
+    user_id: Uid,
+    payload: Json<AcceptPayload>,
+) -> Result<Json<UserMember>, ApiError> {
+    let user_id: Uuid = user_id.into();
+    ACCEPT_LIMITER.check(&user_id.to_string())?;
+    let user_member = MemberInvitations::accept(&conn, user_id, payload.into_inner().code).await?;
+    Ok(Json(user_member))
+}
@@ -1,21 +1,80 @@
-use crate::db::member::UserMember;
-use crate::db::member::{MemberQuery, Members, NewMember};
 use crate::db::BpRecordConn;
+use crate::db::member::{MemberChanges, MemberRole, Members, NewMember};
+use crate::db::member::{MemberListItem, MemberOrder, UserMember};
+use crate::db::quota::MemberQuota;
+use crate::db::record::Records;
 use crate::error::api::ApiError;
+use crate::model::bp::BpAssessment;
+use crate::storage::Storage;
+use crate::util::avatar::{self, AVATAR_MAX_SIZE};
+use crate::util::json::Json;
 use crate::util::jwt::Uid;
+use crate::util::secret::random_token;
+use rocket::State;
+use rocket::form::Form;
+use rocket::fs::TempFile;
 use rocket::routes;
-use rocket::serde::json::Json;
+use rocket::tokio::io::AsyncReadExt;
+use rocket::tokio::task;
+use uuid::Uuid;
 
 pub fn routes() -> Vec<rocket::Route> {
-    routes![members, add_member, edit_member, delete_member, detail]
+    routes![
+        members,
+        quota,
+        add_member,
+        edit_member,
+        delete_member,
+        detail,
+        target,
+        upload_avatar,
+        delete_avatar,
+        reorder,
+        pin,
+        unpin
+    ]
 }
 
 #[get("/all")]
-async fn members(conn: BpRecordConn, user_id: Uid) -> Result<Json<Vec<Members>>, ApiError> {
+async fn members(conn: BpRecordConn, user_id: Uid) -> Result<Json<Vec<MemberListItem>>, ApiError> {
     let member_list = UserMember::get_user_members(&conn, user_id.into()).await?;
     Ok(Json(member_list))
 }
 
+#[put("/order", data = "<member_order>")]
+async fn reorder(
+    conn: BpRecordConn,
+    user_id: Uid,
+    member_order: Json<MemberOrder>,
+) -> Result<Json<Vec<MemberListItem>>, ApiError> {
+    let user_id: Uuid = user_id.into();
+    UserMember::reorder(&conn, user_id, member_order.into_inner()).await?;
+    let member_list = UserMember::get_user_members(&conn, user_id).await?;
+    Ok(Json(member_list))
+}
+
+#[put("/<member_id>/pin")]
+async fn pin(conn: BpRecordConn, user_id: Uid, member_id: Uid) -> Result<(), ApiError> {
+    let user_member =
+        Members::check_user(&conn, user_id.into(), member_id.into(), MemberRole::Viewer).await?;
+    UserMember::pin(&conn, user_member, true).await?;
+    Ok(())
+}
+
+#[delete("/<member_id>/pin")]
+async fn unpin(conn: BpRecordConn, user_id: Uid, member_id: Uid) -> Result<(), ApiError> {
+    let user_member =
+        Members::check_user(&conn, user_id.into(), member_id.into(), MemberRole::Viewer).await?;
+    UserMember::pin(&conn, user_member, false).await?;
+    Ok(())
+}
+
+#[get("/quota")]
+async fn quota(conn: BpRecordConn, user_id: Uid) -> Result<Json<MemberQuota>, ApiError> {
+    let quota = MemberQuota::detail(&conn, user_id.into()).await?;
+    Ok(Json(quota))
+}
+
 #[post("/", data = "<new_member>")]
 async fn add_member(
     conn: BpRecordConn,
@@ -26,22 +85,42 @@ async fn add_member(
     Ok(Json(member))
 }
 
-#[put("/<member_id>", data = "<new_member>")]
+#[put("/<member_id>", data = "<changes>")]
 async fn edit_member(
     conn: BpRecordConn,
     user_id: Uid,
     member_id: Uid,
-    new_member: Json<NewMember>,
+    changes: Json<MemberChanges>,
 ) -> Result<Json<Members>, ApiError> {
-    let user_member = Members::check_user(&conn, user_id.into(), member_id.into()).await?;
-    let member = Members::update(&conn, user_member.member_id, new_member.into_inner()).await?;
+    let user_member =
+        Members::check_user(&conn, user_id.into(), member_id.into(), MemberRole::Editor).await?;
+    let member = Members::update(&conn, user_member.member_id, changes.into_inner()).await?;
     Ok(Json(member))
 }
 
+#[derive(FromForm)]
+struct AvatarForm<'r> {
+    avatar: TempFile<'r>,
+}
+
 #[delete("/<member_id>")]
-async fn delete_member(conn: BpRecordConn, user_id: Uid, member_id: Uid) -> Result<(), ApiError> {
-    let user_member = Members::check_user(&conn, user_id.into(), member_id.into()).await?;
-    Members::delete(&conn, user_member.user_id, user_member.member_id).await?;
+async fn delete_member(
+    conn: BpRecordConn,
+    storage: &State<Box<dyn Storage>>,
+    user_id: Uid,
+    member_id: Uid,
+) -> Result<(), ApiError> {
+    let user_member =
+        Members::check_user(&conn, user_id.into(), member_id.into(), MemberRole::Viewer).await?;
+    let member = Members::detail(&conn, user_member.member_id).await?;
+    let is_owner = user_member.role()? == MemberRole::Owner;
+    Members::delete(&conn, user_member).await?;
+    if is_owner
+        && let Some(key) = member.avatar_key
+        && let Err(err) = storage.delete(&key).await
+    {
+        error!("failed to delete avatar {}: {:?}", key, err);
+    }
     Ok(())
 }
 
@@ -51,17 +130,70 @@ async fn detail(
     user_id: Uid,
     member_id: Uid,
 ) -> Result<Json<Members>, ApiError> {
-    let user_member = Members::check_user(&conn, user_id.into(), member_id.into()).await?;
+    let user_member =
+        Members::check_user(&conn, user_id.into(), member_id.into(), MemberRole::Viewer).await?;
     let member = Members::detail(&conn, user_member.member_id).await?;
     Ok(Json(member))
 }
 
-#[allow(unused)]
-#[get("/?<query..>")]
-async fn all(
+#[get("/<member_id>/target")]
+async fn target(
     conn: BpRecordConn,
-    query: Option<MemberQuery>,
-) -> Result<Json<Vec<Members>>, ApiError> {
-    let member_list = Members::select(&conn, query).await?;
-    Ok(Json(member_list))
+    user_id: Uid,
+    member_id: Uid,
+) -> Result<Json<BpAssessment>, ApiError> {
+    let user_member =
+        Members::check_user(&conn, user_id.into(), member_id.into(), MemberRole::Viewer).await?;
+    let member = Members::detail(&conn, user_member.member_id).await?;
+    let latest = Records::latest(&conn, member.id).await?;
+    Ok(Json(BpAssessment::new(&member, latest)))
+}
+
+#[put("/<member_id>/avatar", data = "<form>")]
+async fn upload_avatar(
+    conn: BpRecordConn,
+    storage: &State<Box<dyn Storage>>,
+    user_id: Uid,
+    member_id: Uid,
+    form: Form<AvatarForm<'_>>,
+) -> Result<Json<Members>, ApiError> {
+    let user_member =
+        Members::check_user(&conn, user_id.into(), member_id.into(), MemberRole::Editor).await?;
+    let file = &form.avatar;
+    if file.len() > *AVATAR_MAX_SIZE {
+        return Err(ApiError::BadRequest(String::from("头像文件过大")));
+    }
+    let mut bytes = Vec::with_capacity(file.len() as usize);
+    file.open().await?.read_to_end(&mut bytes).await?;
+    let thumbnail = task::spawn_blocking(move || avatar::thumbnail(&bytes)).await??;
+    let member = Members::detail(&conn, user_member.member_id).await?;
+    let key = format!("avatars/{}-{}.png", member.id, random_token(16));
+    storage.put(&key, thumbnail).await?;
+    let url = storage.url(&key);
+    let updated = Members::update_avatar(&conn, member.id, Some(key), Some(url)).await?;
+    if let Some(old_key) = member.avatar_key
+        && let Err(err) = storage.delete(&old_key).await
+    {
+        error!("failed to delete avatar {}: {:?}", old_key, err);
+    }
+    Ok(Json(updated))
+}
+
+#[delete("/<member_id>/avatar")]
+async fn delete_avatar(
+    conn: BpRecordConn,
+    storage: &State<Box<dyn Storage>>,
+    user_id: Uid,
+    member_id: Uid,
+) -> Result<Json<Members>, ApiError> {
+    let user_member =
+        Members::check_user(&conn, user_id.into(), member_id.into(), MemberRole::Editor).await?;
+    let member = Members::detail(&conn, user_member.member_id).await?;
+    let updated = Members::update_avatar(&conn, member.id, None, None).await?;
+    if let Some(key) = member.avatar_key
+        && let Err(err) = storage.delete(&key).await
+    {
+        error!("failed to delete avatar {}: {:?}", key, err);
+    }
+    Ok(Json(updated))
 }
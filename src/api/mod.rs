@@ -1,21 +1,62 @@
-use crate::db::user::{NewUser, UserQuery, Users};
+use crate::db::identity::UserIdentities;
+use crate::db::login::LoginEvents;
+use crate::db::sms::SmsCodes;
 use crate::error::api::ApiError;
 use crate::error::auth::AuthError;
-use crate::model::auth::{AuthBody, WxUser};
-use crate::util::jwt::{Claims, KEYS};
+use crate::identity::IdentityProvider;
+use crate::identity::password::{PasswordCredentials, PasswordProvider};
+use crate::identity::sms::{SmsCredentials, SmsProvider, normalize_phone};
+use crate::identity::wechat::WechatProvider;
+use crate::model::auth::AuthBody;
+use crate::sms::SmsSender;
+use crate::util::client::ClientInfo;
+use crate::util::json::Json;
+use crate::util::rate_limit::RateLimiter;
+use crate::wechat::WechatClient;
 use crate::BpRecordConn;
-use jsonwebtoken::{encode, Header};
-use rocket::serde::json::Json;
+use lazy_static::lazy_static;
+use rocket::State;
 use rocket::serde::Deserialize;
-use serde_json::Value;
 use std::env;
+use std::time::Duration;
 
+pub mod admin;
+pub mod fhir;
+pub mod invitation;
 pub mod member;
 pub mod user;
 pub mod record;
+pub mod search;
+pub mod token;
+pub mod well_known;
+
+lazy_static! {
+    static ref PASSWORD_LOGIN_LIMITER: RateLimiter = {
+        let limit = env::var("PASSWORD_LOGIN_LIMIT")
+            .unwrap_or_else(|_| "5".to_owned())
+            .parse::<usize>()
+            .unwrap();
+        let window = env::var("PASSWORD_LOGIN_WINDOW")
+            .unwrap_or_else(|_| "600".to_owned())
+            .parse::<u64>()
+            .unwrap();
+        RateLimiter::new(limit, Duration::from_secs(window))
+    };
+    static ref SMS_REQUEST_LIMITER: RateLimiter = {
+        let limit = env::var("SMS_REQUEST_LIMIT")
+            .unwrap_or_else(|_| "5".to_owned())
+            .parse::<usize>()
+            .unwrap();
+        let window = env::var("SMS_REQUEST_WINDOW")
+            .unwrap_or_else(|_| "3600".to_owned())
+            .parse::<u64>()
+            .unwrap();
+        RateLimiter::new(limit, Duration::from_secs(window))
+    };
+}
 
 pub fn routes() -> Vec<rocket::Route> {
-    routes![login]
+    routes![login, password_login, register, sms_request, sms_verify]
 }
 
 #[derive(Deserialize)]
@@ -23,70 +64,88 @@ struct LoginPayload {
     code: String,
 }
 
+#[derive(Deserialize)]
+struct SmsPayload {
+    phone: String,
+}
+
+async fn sign_in<P: IdentityProvider>(
+    conn: &BpRecordConn,
+    client: ClientInfo,
+    provider: &P,
+    credentials: P::Credentials,
+) -> Result<AuthBody, ApiError> {
+    let identity = provider.authenticate(conn, credentials).await?;
+    let provider = identity.provider;
+    let user = UserIdentities::sign_in(conn, identity).await?;
+    if user.disabled_at.is_some() {
+        return Err(AuthError::UserDisabled.into());
+    }
+    LoginEvents::insert(conn, user.id, provider, client).await?;
+    token::issue(conn, user.id).await
+}
+
 #[post("/login", data = "<payload>")]
 async fn login(
     conn: BpRecordConn,
+    client: ClientInfo,
+    wechat: &State<WechatClient>,
     payload: Json<LoginPayload>,
 ) -> Result<Json<AuthBody>, ApiError> {
-    let wx_user = wx_login(payload.into_inner().code).await?;
-
-    let user_query = UserQuery {
-        openid: Option::from(wx_user.openid.clone()),
-        session_key: None,
-    };
-    let user = Users::select(&conn, Option::from(user_query)).await;
-    let user = match user {
-        Ok(user) => {
-            let new_user = NewUser {
-                openid: wx_user.openid,
-                session_key: wx_user.session_key,
-            };
-            if user.is_empty() {
-                Users::insert(&conn, new_user).await?
-            } else {
-                if let Some(user) = user.first() {
-                    Users::update(&conn, user.id, new_user).await?
-                } else {
-                    return Err(AuthError::MissingCredentials.into());
-                }
-            }
-        }
-        Err(err) => return Err(ApiError::from(err))
-    };
-
-    let claims = Claims::new(user.id.to_string());
-    let token = encode(&Header::default(), &claims, &KEYS.encoding)
-        .map_err(|_| AuthError::TokenCreation)?;
-    info!("token: {:?}", token);
-    Ok(Json(AuthBody::new(token, claims.exp)))
+    let provider = WechatProvider(wechat.inner());
+    let auth_body = sign_in(&conn, client, &provider, payload.into_inner().code).await?;
+    Ok(Json(auth_body))
 }
 
-async fn wx_login(code: String) -> Result<WxUser, ApiError> {
-    if code.is_empty() {
-        return Err(AuthError::MissingCredentials.into());
-    }
-
-    let app_id = env::var("APP_ID").expect("APP_ID must be set");
-    let app_secret = env::var("APP_SECRET").expect("APP_SECRET must be set");
-    let code2session_url = format!(
-        "https://api.weixin.qq.com/sns/jscode2session?appid={}&secret={}&js_code={}&grant_type=authorization_code",
-        app_id, app_secret, code
-    );
-    let resp = reqwest::get(code2session_url)
-        .await
-        .map_err(|_| AuthError::WrongCredentials)?
-        .json::<Value>()
-        .await
-        .map_err(|_| AuthError::WrongCredentials)?;
+#[post("/login/password", data = "<credentials>")]
+async fn password_login(
+    conn: BpRecordConn,
+    client: ClientInfo,
+    credentials: Json<PasswordCredentials>,
+) -> Result<Json<AuthBody>, ApiError> {
+    PASSWORD_LOGIN_LIMITER.check(&credentials.username.trim().to_lowercase())?;
+    let auth_body = sign_in(&conn, client, &PasswordProvider, credentials.into_inner()).await?;
+    Ok(Json(auth_body))
+}
 
-    info!("wx_login code: {}, resp: {:?}", code, resp);
+#[post("/register", data = "<credentials>")]
+async fn register(
+    conn: BpRecordConn,
+    client: ClientInfo,
+    credentials: Json<PasswordCredentials>,
+) -> Result<Json<AuthBody>, ApiError> {
+    // `:` never occurs in a username, so this cannot share a bucket with one.
+    PASSWORD_LOGIN_LIMITER.check(&format!(
+        "register:{}",
+        client.ip.as_deref().unwrap_or_default()
+    ))?;
+    let identity = PasswordProvider.register(credentials.into_inner()).await?;
+    let provider = identity.provider;
+    let user = UserIdentities::register(&conn, identity).await?;
+    LoginEvents::insert(&conn, user.id, provider, client).await?;
+    let auth_body = token::issue(&conn, user.id).await?;
+    Ok(Json(auth_body))
+}
 
-    let wx_user =
-        serde_json::from_value::<WxUser>(resp).map_err(|_| AuthError::WrongCredentials)?;
+#[post("/login/sms/request", data = "<payload>")]
+async fn sms_request(
+    conn: BpRecordConn,
+    sender: &State<Box<dyn SmsSender>>,
+    payload: Json<SmsPayload>,
+) -> Result<(), ApiError> {
+    let phone = normalize_phone(&payload.phone)?;
+    SMS_REQUEST_LIMITER.check(&phone)?;
+    let code = SmsCodes::insert(&conn, phone.clone()).await?;
+    sender.send_code(&phone, &code).await?;
+    Ok(())
+}
 
-    if wx_user.openid.is_empty() {
-        Err(AuthError::WrongCredentials.into())
-    } else {
-        Ok(wx_user)
-    }
+#[post("/login/sms/verify", data = "<credentials>")]
+async fn sms_verify(
+    conn: BpRecordConn,
+    client: ClientInfo,
+    credentials: Json<SmsCredentials>,
+) -> Result<Json<AuthBody>, ApiError> {
+    let auth_body = sign_in(&conn, client, &SmsProvider, credentials.into_inner()).await?;
+    Ok(Json(auth_body))
 }
@@ -1,12 +1,19 @@
-use crate::db::member::Members;
-use crate::db::record::{NewRecord, Records};
 use crate::db::BpRecordConn;
+use crate::db::member::{MemberRole, Members};
+use crate::db::record::{ArchivedRecords, NewRecord, Records};
 use crate::error::api::ApiError;
+use crate::util::json::Json;
 use crate::util::jwt::Uid;
-use rocket::serde::json::Json;
 
 pub fn routes() -> Vec<rocket::Route> {
-    routes![records, add_record, edit_record, delete_record, detail]
+    routes![
+        records,
+        archive,
+        add_record,
+        edit_record,
+        delete_record,
+        detail
+    ]
 }
 
 #[get("/<member_id>")]
@@ -15,11 +22,24 @@ async fn records(
     user_id: Uid,
     member_id: Uid,
 ) -> Result<Json<Vec<Records>>, ApiError> {
-    let user_member = Members::check_user(&conn, user_id.into(), member_id.into()).await?;
+    let user_member =
+        Members::check_user(&conn, user_id.into(), member_id.into(), MemberRole::Viewer).await?;
     let record_list = Records::get_member_record(&conn, user_member.member_id).await?;
     Ok(Json(record_list))
 }
 
+#[get("/<member_id>/archive")]
+async fn archive(
+    conn: BpRecordConn,
+    user_id: Uid,
+    member_id: Uid,
+) -> Result<Json<Vec<ArchivedRecords>>, ApiError> {
+    let user_member =
+        Members::check_user(&conn, user_id.into(), member_id.into(), MemberRole::Viewer).await?;
+    let record_list = ArchivedRecords::get_member_archive(&conn, user_member.member_id).await?;
+    Ok(Json(record_list))
+}
+
 #[post("/<member_id>", data = "<new_record>")]
 async fn add_record(
     conn: BpRecordConn,
@@ -27,7 +47,8 @@ async fn add_record(
     member_id: Uid,
     new_record: Json<NewRecord>,
 ) -> Result<Json<Records>, ApiError> {
-    let user_member = Members::check_user(&conn, user_id.into(), member_id.into()).await?;
+    let user_member =
+        Members::check_user(&conn, user_id.into(), member_id.into(), MemberRole::Editor).await?;
     let record = Records::insert(&conn, user_member.member_id, new_record.into_inner()).await?;
     Ok(Json(record))
 }
@@ -40,7 +61,8 @@ async fn edit_record(
     record_id: Uid,
     new_record: Json<NewRecord>,
 ) -> Result<Json<Records>, ApiError> {
-    let user_member = Members::check_user(&conn, user_id.into(), member_id.into()).await?;
+    let user_member =
+        Members::check_user(&conn, user_id.into(), member_id.into(), MemberRole::Editor).await?;
     let record = Records::update(
         &conn,
         record_id.into(),
@@ -58,8 +80,9 @@ async fn delete_record(
     member_id: Uid,
     record_id: Uid,
 ) -> Result<(), ApiError> {
-    Members::check_user(&conn, user_id.into(), member_id.into()).await?;
-    Records::delete(&conn, record_id.into()).await?;
+    let user_member =
+        Members::check_user(&conn, user_id.into(), member_id.into(), MemberRole::Editor).await?;
+    Records::delete(&conn, user_member.member_id, record_id.into()).await?;
     Ok(())
 }
 
@@ -70,7 +93,8 @@ async fn detail(
     member_id: Uid,
     record_id: Uid,
 ) -> Result<Json<Records>, ApiError> {
-    Members::check_user(&conn, user_id.into(), member_id.into()).await?;
-    let record = Records::detail(&conn, record_id.into()).await?;
+    let user_member =
+        Members::check_user(&conn, user_id.into(), member_id.into(), MemberRole::Viewer).await?;
+    let record = Records::detail(&conn, user_member.member_id, record_id.into()).await?;
     Ok(Json(record))
 }
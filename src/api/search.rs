@@ -0,0 +1,19 @@
+use crate::db::BpRecordConn;
+use crate::db::search::SearchResult;
+use crate::error::api::ApiError;
+use crate::util::json::Json;
+use crate::util::jwt::Uid;
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![search]
+}
+
+#[get("/?<q>")]
+async fn search(
+    conn: BpRecordConn,
+    user_id: Uid,
+    q: String,
+) -> Result<Json<SearchResult>, ApiError> {
+    let result = SearchResult::search(&conn, user_id.into(), q).await?;
+    Ok(Json(result))
+}
@@ -0,0 +1,70 @@
+use crate::db::BpRecordConn;
+use crate::db::revocation::RevokedTokens;
+use crate::db::token::RefreshTokens;
+use crate::error::api::ApiError;
+use crate::error::auth::AuthError;
+use crate::model::auth::AuthBody;
+use crate::util::json::Json;
+use crate::util::jwt::{Claims, KEYS, Uid};
+use rocket::serde::Deserialize;
+use uuid::Uuid;
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![refresh, logout, logout_all]
+}
+
+#[derive(Deserialize)]
+struct RefreshPayload {
+    refresh_token: String,
+}
+
+pub async fn issue(conn: &BpRecordConn, user_id: Uuid) -> Result<AuthBody, ApiError> {
+    let refresh_token = RefreshTokens::insert(conn, user_id).await?;
+    access_token(conn, user_id, refresh_token).await
+}
+
+async fn access_token(
+    conn: &BpRecordConn,
+    user_id: Uuid,
+    refresh_token: String,
+) -> Result<AuthBody, ApiError> {
+    let generation = RevokedTokens::generation(conn, user_id)
+        .await?
+        .ok_or(AuthError::WrongCredentials)?;
+    let claims = Claims::new(user_id.to_string(), generation);
+    let token = KEYS.encode(&claims)?;
+    Ok(AuthBody::new(token, claims.exp, refresh_token))
+}
+
+#[post("/refresh", data = "<payload>")]
+async fn refresh(
+    conn: BpRecordConn,
+    payload: Json<RefreshPayload>,
+) -> Result<Json<AuthBody>, ApiError> {
+    let refresh_token = RefreshTokens::rotate(&conn, payload.into_inner().refresh_token).await?;
+    let auth_body = access_token(&conn, refresh_token.user_id, refresh_token.token).await?;
+    Ok(Json(auth_body))
+}
+
+#[post("/logout", data = "<payload>")]
+async fn logout(
+    conn: BpRecordConn,
+    user_id: Uid,
+    claims: Claims,
+    payload: Option<Json<RefreshPayload>>,
+) -> Result<(), ApiError> {
+    let user_id: Uuid = user_id.into();
+    RevokedTokens::revoke(&conn, user_id, &claims).await?;
+    if let Some(payload) = payload {
+        RefreshTokens::revoke(&conn, user_id, payload.into_inner().refresh_token).await?;
+    }
+    Ok(())
+}
+
+#[post("/logout/all")]
+async fn logout_all(conn: BpRecordConn, user_id: Uid) -> Result<(), ApiError> {
+    let user_id: Uuid = user_id.into();
+    RevokedTokens::revoke_all(&conn, user_id).await?;
+    RefreshTokens::revoke_all(&conn, user_id).await?;
+    Ok(())
+}
@@ -1,48 +1,249 @@
-use crate::db::user::{NewUser, UserQuery, Users};
-use crate::db::BpRecordConn;
+use crate::db::erasure::{AccountDeletions, DeletionConfirmation};
+use crate::db::export::{DataExports, ExportTicket};
+use crate::db::identity::UserIdentities;
+use crate::db::profile::{NewProfile, UserProfiles};
+use crate::db::user::{UserDetail, Users};
+use crate::db::{BpRecordConn, BpRecordPool};
 use crate::error::api::ApiError;
+use crate::identity::IdentityProvider;
+use crate::identity::password::{PasswordCredentials, PasswordProvider};
+use crate::identity::wechat::WechatProvider;
+use crate::job;
+use crate::storage::ExportStorage;
+use crate::util::json::Json;
 use crate::util::jwt::Uid;
+use crate::wechat::WechatClient;
+use crate::wechat::crypto::{decrypt_phone, decrypt_user_info};
+use rocket::State;
+use rocket::http::Header;
 use rocket::routes;
-use rocket::serde::json::Json;
+use serde::Deserialize;
+use uuid::Uuid;
 
 pub fn routes() -> Vec<rocket::Route> {
-    routes![detail]
+    routes![
+        detail,
+        profile,
+        update_profile,
+        identities,
+        link_wechat,
+        link_password,
+        unlink,
+        wechat_phone,
+        wechat_profile,
+        request_deletion,
+        deletion,
+        cancel_deletion,
+        delete,
+        export,
+        export_status,
+        download_export
+    ]
+}
+
+#[derive(Deserialize)]
+struct WechatPayload {
+    code: String,
+}
+
+#[derive(Responder)]
+#[response(content_type = "application/zip")]
+struct ExportArchive {
+    inner: Vec<u8>,
+    disposition: Header<'static>,
+}
+
+#[derive(Deserialize)]
+struct DeletionPayload {
+    confirmation: String,
+}
+
+#[derive(Deserialize)]
+struct EncryptedPayload {
+    encrypted_data: String,
+    iv: String,
 }
 
 #[get("/")]
-async fn detail(conn: BpRecordConn, id: Uid) -> Result<Json<Users>, ApiError> {
-    let user = Users::detail(&conn, id.into()).await?;
+async fn detail(conn: BpRecordConn, id: Uid) -> Result<Json<UserDetail>, ApiError> {
+    let user = Users::profile(&conn, id.into()).await?;
     Ok(Json(user))
 }
 
-#[allow(unused)]
-#[get("/?<query..>")]
-async fn all(conn: BpRecordConn, query: Option<UserQuery>) -> Result<Json<Vec<Users>>, ApiError> {
-    let user_list = Users::select(&conn, query).await?;
-    Ok(Json(user_list))
+#[get("/profile")]
+async fn profile(conn: BpRecordConn, id: Uid) -> Result<Json<UserProfiles>, ApiError> {
+    let profile = UserProfiles::detail(&conn, id.into()).await?;
+    Ok(Json(profile))
 }
 
-#[allow(unused)]
-#[post("/", data = "<new_user>")]
-async fn add(conn: BpRecordConn, new_user: Json<NewUser>) -> Result<Json<Users>, ApiError> {
-    let user = Users::insert(&conn, new_user.into_inner()).await?;
-    Ok(Json(user))
+#[put("/profile", data = "<new_profile>")]
+async fn update_profile(
+    conn: BpRecordConn,
+    id: Uid,
+    new_profile: Json<NewProfile>,
+) -> Result<Json<UserProfiles>, ApiError> {
+    let profile = UserProfiles::update(&conn, id.into(), new_profile.into_inner()).await?;
+    Ok(Json(profile))
+}
+
+#[get("/identities")]
+async fn identities(conn: BpRecordConn, id: Uid) -> Result<Json<Vec<UserIdentities>>, ApiError> {
+    let identity_list = UserIdentities::get_user_identities(&conn, id.into()).await?;
+    Ok(Json(identity_list))
 }
 
-#[allow(unused)]
-#[put("/", data = "<new_user>")]
-async fn modify(
+#[post("/identities/wechat", data = "<payload>")]
+async fn link_wechat(
     conn: BpRecordConn,
+    wechat: &State<WechatClient>,
     id: Uid,
-    new_user: Json<NewUser>,
-) -> Result<Json<Users>, ApiError> {
-    let user = Users::update(&conn, id.into(), new_user.into_inner()).await?;
-    Ok(Json(user))
+    payload: Json<WechatPayload>,
+) -> Result<Json<UserIdentities>, ApiError> {
+    let identity = WechatProvider(wechat.inner())
+        .authenticate(&conn, payload.into_inner().code)
+        .await?;
+    let identity = UserIdentities::link(&conn, id.into(), identity).await?;
+    Ok(Json(identity))
+}
+
+#[post("/identities/password", data = "<credentials>")]
+async fn link_password(
+    conn: BpRecordConn,
+    id: Uid,
+    credentials: Json<PasswordCredentials>,
+) -> Result<Json<UserIdentities>, ApiError> {
+    let identity = PasswordProvider.register(credentials.into_inner()).await?;
+    let identity = UserIdentities::link(&conn, id.into(), identity).await?;
+    Ok(Json(identity))
+}
+
+#[delete("/identities/<identity_id>")]
+async fn unlink(conn: BpRecordConn, id: Uid, identity_id: Uid) -> Result<(), ApiError> {
+    UserIdentities::unlink(&conn, id.into(), identity_id.into()).await?;
+    Ok(())
+}
+
+async fn session_key(conn: &BpRecordConn, id: Uuid) -> Result<String, ApiError> {
+    Users::session_key(conn, id)
+        .await?
+        .ok_or_else(|| ApiError::BadRequest(String::from("请先使用微信登录")))
+}
+
+#[post("/wechat/phone", data = "<payload>")]
+async fn wechat_phone(
+    conn: BpRecordConn,
+    wechat: &State<WechatClient>,
+    id: Uid,
+    payload: Json<EncryptedPayload>,
+) -> Result<Json<UserDetail>, ApiError> {
+    let id: Uuid = id.into();
+    let session_key = session_key(&conn, id).await?;
+    let phone = decrypt_phone(
+        &session_key,
+        &payload.encrypted_data,
+        &payload.iv,
+        wechat.app_id()?,
+    )?;
+    Users::update_phone(&conn, id, phone.pure_phone_number).await?;
+    Ok(Json(Users::profile(&conn, id).await?))
+}
+
+#[post("/wechat/profile", data = "<payload>")]
+async fn wechat_profile(
+    conn: BpRecordConn,
+    wechat: &State<WechatClient>,
+    id: Uid,
+    payload: Json<EncryptedPayload>,
+) -> Result<Json<UserDetail>, ApiError> {
+    let id: Uuid = id.into();
+    let session_key = session_key(&conn, id).await?;
+    let user_info = decrypt_user_info(
+        &session_key,
+        &payload.encrypted_data,
+        &payload.iv,
+        wechat.app_id()?,
+    )?;
+    Users::update_profile(&conn, id, user_info.nick_name, user_info.avatar_url).await?;
+    Ok(Json(Users::profile(&conn, id).await?))
+}
+
+/// Starts building a ZIP of everything stored about the user; poll
+/// `/export/<export_id>` and fetch it with the returned download token.
+#[get("/export")]
+async fn export(
+    conn: BpRecordConn,
+    pool: &State<BpRecordPool>,
+    storage: &State<ExportStorage>,
+    id: Uid,
+) -> Result<Json<ExportTicket>, ApiError> {
+    let ticket = DataExports::insert(&conn, id.into()).await?;
+    job::export::spawn(pool.inner().clone(), storage.0.clone(), &ticket.export);
+    Ok(Json(ticket))
+}
+
+#[get("/export/<export_id>")]
+async fn export_status(
+    conn: BpRecordConn,
+    id: Uid,
+    export_id: Uid,
+) -> Result<Json<DataExports>, ApiError> {
+    let export = DataExports::detail(&conn, id.into(), export_id.into()).await?;
+    Ok(Json(export))
 }
 
-#[allow(unused)]
-#[delete("/")]
-async fn delete(conn: BpRecordConn, id: Uid) -> Result<(), ApiError> {
-    Users::delete(&conn, id.into()).await?;
+/// Authorized by the download token alone, so the link can be opened
+/// outside the app.
+#[get("/export/download?<token>")]
+async fn download_export(
+    conn: BpRecordConn,
+    storage: &State<ExportStorage>,
+    token: String,
+) -> Result<ExportArchive, ApiError> {
+    let export = DataExports::find_ready(&conn, token).await?;
+    let storage_key = export.storage_key.ok_or(ApiError::NotFound)?;
+    let archive = storage.0.get(&storage_key).await?;
+    Ok(ExportArchive {
+        inner: archive,
+        disposition: Header::new(
+            "Content-Disposition",
+            format!(
+                "attachment; filename=\"export-{}.zip\"",
+                export.created_at.format("%Y%m%d%H%M%S")
+            ),
+        ),
+    })
+}
+
+#[post("/deletion")]
+async fn request_deletion(
+    conn: BpRecordConn,
+    id: Uid,
+) -> Result<Json<DeletionConfirmation>, ApiError> {
+    let confirmation = AccountDeletions::request(&conn, id.into()).await?;
+    Ok(Json(confirmation))
+}
+
+#[get("/deletion")]
+async fn deletion(conn: BpRecordConn, id: Uid) -> Result<Json<Option<AccountDeletions>>, ApiError> {
+    let deletion = AccountDeletions::status(&conn, id.into()).await?;
+    Ok(Json(deletion))
+}
+
+#[delete("/deletion")]
+async fn cancel_deletion(conn: BpRecordConn, id: Uid) -> Result<(), ApiError> {
+    AccountDeletions::cancel(&conn, id.into()).await?;
     Ok(())
 }
+
+/// Schedules the account for erasure after the grace period; the
+/// confirmation comes from `POST /deletion`.
+#[delete("/", data = "<payload>")]
+async fn delete(
+    conn: BpRecordConn,
+    id: Uid,
+    payload: Json<DeletionPayload>,
+) -> Result<Json<AccountDeletions>, ApiError> {
+    let deletion =
+        AccountDeletions::confirm(&conn, id.into(), payload.into_inner().confirmation).await?;
+    Ok(Json(deletion))
+}
@@ -0,0 +1,12 @@
+use crate::util::json::Json;
+use crate::util::jwt::KEYS;
+use jsonwebtoken::jwk::JwkSet;
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![jwks]
+}
+
+#[get("/jwks.json")]
+async fn jwks() -> Json<JwkSet> {
+    Json(KEYS.jwks())
+}
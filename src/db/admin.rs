@@ -0,0 +1,505 @@
+use crate::db::BpRecordConn;
+use crate::db::login::LoginEvents;
+use crate::db::member::{Members, MergeReport};
+use crate::db::quota::MemberQuota;
+use crate::db::revocation::RevokedTokens;
+use crate::db::search::escape_like;
+use crate::db::user::Users;
+use crate::error::api::ApiError;
+use crate::schema::{
+    admin_audit_logs, members, quota_plans, records, records_archive, refresh_tokens,
+    user_identities, user_member, user_quotas, users,
+};
+use crate::util::jwt::{Admin, Uid};
+use crate::util::serde_time_format;
+use chrono::NaiveDateTime;
+use diesel::dsl::{count_star, max};
+use diesel::prelude::*;
+use diesel::{PgConnection, Queryable, Selectable};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::env;
+use uuid::Uuid;
+
+lazy_static! {
+    pub static ref ADMIN_PAGE_SIZE: i64 = {
+        env::var("ADMIN_PAGE_SIZE")
+            .unwrap_or_else(|_| "50".to_owned())
+            .parse::<i64>()
+            .unwrap()
+    };
+}
+
+/// Value of `users.role` for administrators. There is no API to grant it;
+/// it is set directly in the database.
+pub const ADMIN: &str = "admin";
+
+#[derive(Debug, Clone, Copy)]
+pub enum AdminAction {
+    SearchUsers,
+    ViewMembers,
+    ViewLogins,
+    DisableUser,
+    EnableUser,
+    UpdateQuota,
+    MergeMembers,
+}
+
+impl AdminAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AdminAction::SearchUsers => "search_users",
+            AdminAction::ViewMembers => "view_members",
+            AdminAction::ViewLogins => "view_logins",
+            AdminAction::DisableUser => "disable_user",
+            AdminAction::EnableUser => "enable_user",
+            AdminAction::UpdateQuota => "update_quota",
+            AdminAction::MergeMembers => "merge_members",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Identifiable, Queryable, Selectable)]
+#[diesel(
+    table_name = crate::schema::admin_audit_logs,
+    primary_key(id),
+    check_for_backend(diesel::pg::Pg),
+)]
+pub struct AdminAuditLogs {
+    pub id: Uuid,
+    pub admin_id: Uuid,
+    pub action: String,
+    pub target_user_id: Option<Uuid>,
+    pub detail: Value,
+    #[serde(with = "serde_time_format")]
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AdminMember {
+    #[serde(flatten)]
+    pub member: Members,
+    pub role: String,
+    pub record_num: i64,
+    pub archived_num: i64,
+    #[serde(with = "serde_time_format::optional")]
+    pub latest_record_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, FromForm)]
+pub struct UserSearch {
+    #[field(name = "q")]
+    pub keyword: Option<String>,
+    pub page: Option<i64>,
+}
+
+#[derive(Debug, FromForm)]
+pub struct AuditQuery {
+    pub admin_id: Option<Uid>,
+    pub user_id: Option<Uid>,
+    pub page: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct QuotaUpdate {
+    #[serde(default)]
+    pub plan_id: Option<Uuid>,
+    #[serde(default)]
+    pub member_limit: Option<i32>,
+}
+
+fn offset(page: Option<i64>) -> i64 {
+    page.unwrap_or(0).max(0) * *ADMIN_PAGE_SIZE
+}
+
+fn find_user(x: &mut PgConnection, user_id: Uuid) -> Result<Users, ApiError> {
+    users::table
+        .find(user_id)
+        .get_result::<Users>(x)
+        .optional()?
+        .ok_or(ApiError::NotFound)
+}
+
+impl AdminAuditLogs {
+    fn create(
+        x: &mut PgConnection,
+        admin_id: Uuid,
+        action: AdminAction,
+        target_user_id: Option<Uuid>,
+        detail: Value,
+    ) -> QueryResult<AdminAuditLogs> {
+        diesel::insert_into(admin_audit_logs::table)
+            .values((
+                admin_audit_logs::admin_id.eq(admin_id),
+                admin_audit_logs::action.eq(action.as_str()),
+                admin_audit_logs::target_user_id.eq(target_user_id),
+                admin_audit_logs::detail.eq(detail),
+            ))
+            .get_result::<AdminAuditLogs>(x)
+    }
+
+    pub async fn select(
+        conn: &BpRecordConn,
+        query: AuditQuery,
+    ) -> Result<Vec<AdminAuditLogs>, ApiError> {
+        let mut audit_query = admin_audit_logs::table.into_boxed();
+        if let Some(admin_id) = query.admin_id {
+            audit_query = audit_query.filter(admin_audit_logs::admin_id.eq(Uuid::from(admin_id)));
+        }
+        if let Some(user_id) = query.user_id {
+            audit_query =
+                audit_query.filter(admin_audit_logs::target_user_id.eq(Uuid::from(user_id)));
+        }
+        let offset = offset(query.page);
+        let audit_list = conn
+            .run(move |c| {
+                audit_query
+                    .order(admin_audit_logs::created_at.desc())
+                    .offset(offset)
+                    .limit(*ADMIN_PAGE_SIZE)
+                    .get_results::<AdminAuditLogs>(c)
+            })
+            .await?;
+        Ok(audit_list)
+    }
+}
+
+/// Support operations. Each one writes an `admin_audit_logs` row in the same
+/// transaction, including those that only read.
+impl Admin {
+    /// Matches the keyword against the user id, nickname, phone and the
+    /// subject of any linked identity; without a keyword lists the newest users.
+    pub async fn search_users(
+        &self,
+        conn: &BpRecordConn,
+        search: UserSearch,
+    ) -> Result<Vec<Users>, ApiError> {
+        let admin_id = self.0;
+        let keyword = search
+            .keyword
+            .map(|keyword| keyword.trim().to_owned())
+            .filter(|keyword| !keyword.is_empty());
+        let mut user_query = users::table.into_boxed();
+        if let Some(keyword) = keyword.clone() {
+            if let Ok(id) = keyword.parse::<Uuid>() {
+                user_query = user_query.filter(users::id.eq(id));
+            } else {
+                let pattern = format!("%{}%", escape_like(&keyword));
+                user_query = user_query.filter(
+                    users::nickname
+                        .ilike(pattern.clone())
+                        .or(users::phone.like(pattern))
+                        .or(users::id.nullable().eq_any(
+                            user_identities::table
+                                .filter(user_identities::subject.eq(keyword))
+                                .select(user_identities::user_id.nullable()),
+                        )),
+                );
+            }
+        }
+        let page = search.page;
+        let user_list = conn
+            .run(move |c| {
+                c.transaction(|x| {
+                    let user_list = user_query
+                        .order(users::created_at.desc())
+                        .offset(offset(page))
+                        .limit(*ADMIN_PAGE_SIZE)
+                        .get_results::<Users>(x)?;
+                    AdminAuditLogs::create(
+                        x,
+                        admin_id,
+                        AdminAction::SearchUsers,
+                        None,
+                        json!({ "q": keyword, "page": page }),
+                    )?;
+                    Ok::<Vec<Users>, diesel::result::Error>(user_list)
+                })
+            })
+            .await?;
+        Ok(user_list)
+    }
+
+    pub async fn user_members(
+        &self,
+        conn: &BpRecordConn,
+        user_id: Uuid,
+    ) -> Result<Vec<AdminMember>, ApiError> {
+        let admin_id = self.0;
+        let member_list = conn
+            .run(move |c| {
+                c.transaction(|x| {
+                    find_user(x, user_id)?;
+                    let member_list = members::table
+                        .inner_join(user_member::table)
+                        .filter(user_member::user_id.eq(user_id))
+                        .order(members::created_at.asc())
+                        .select((Members::as_select(), user_member::role))
+                        .get_results::<(Members, String)>(x)?;
+                    let member_ids = member_list
+                        .iter()
+                        .map(|(member, _)| member.id)
+                        .collect::<Vec<_>>();
+                    let record_stats = records::table
+                        .filter(records::member_id.eq_any(&member_ids))
+                        .group_by(records::member_id)
+                        .select((records::member_id, count_star(), max(records::record_at)))
+                        .get_results::<(Uuid, i64, Option<NaiveDateTime>)>(x)?
+                        .into_iter()
+                        .map(|(member_id, num, latest)| (member_id, (num, latest)))
+                        .collect::<HashMap<_, _>>();
+                    let archived_stats = records_archive::table
+                        .filter(records_archive::member_id.eq_any(&member_ids))
+                        .group_by(records_archive::member_id)
+                        .select((records_archive::member_id, count_star()))
+                        .get_results::<(Uuid, i64)>(x)?
+                        .into_iter()
+                        .collect::<HashMap<_, _>>();
+                    AdminAuditLogs::create(
+                        x,
+                        admin_id,
+                        AdminAction::ViewMembers,
+                        Some(user_id),
+                        json!({}),
+                    )?;
+                    Ok::<Vec<AdminMember>, ApiError>(
+                        member_list
+                            .into_iter()
+                            .map(|(member, role)| {
+                                let (record_num, latest_record_at) =
+                                    record_stats.get(&member.id).copied().unwrap_or_default();
+                                let archived_num =
+                                    archived_stats.get(&member.id).copied().unwrap_or_default();
+                                AdminMember {
+                                    member,
+                                    role,
+                                    record_num,
+                                    archived_num,
+                                    latest_record_at,
+                                }
+                            })
+                            .collect(),
+                    )
+                })
+            })
+            .await?;
+        Ok(member_list)
+    }
+
+    pub async fn user_logins(
+        &self,
+        conn: &BpRecordConn,
+        user_id: Uuid,
+        page: Option<i64>,
+    ) -> Result<Vec<LoginEvents>, ApiError> {
+        let admin_id = self.0;
+        let event_list = conn
+            .run(move |c| {
+                c.transaction(|x| {
+                    find_user(x, user_id)?;
+                    let event_list =
+                        LoginEvents::history(x, user_id, offset(page), *ADMIN_PAGE_SIZE)?;
+                    AdminAuditLogs::create(
+                        x,
+                        admin_id,
+                        AdminAction::ViewLogins,
+                        Some(user_id),
+                        json!({ "page": page }),
+                    )?;
+                    Ok::<Vec<LoginEvents>, ApiError>(event_list)
+                })
+            })
+            .await?;
+        Ok(event_list)
+    }
+
+    /// Blocks sign-in and ends every session of the user. Tokens issued
+    /// before this stay invalid even if the account is enabled again.
+    pub async fn disable_user(
+        &self,
+        conn: &BpRecordConn,
+        user_id: Uuid,
+        reason: Option<String>,
+    ) -> Result<Users, ApiError> {
+        let admin_id = self.0;
+        if admin_id == user_id {
+            return Err(ApiError::BadRequest(String::from("不能停用自己的账号")));
+        }
+        let user = conn
+            .run(move |c| {
+                c.transaction(|x| {
+                    if find_user(x, user_id)?.disabled_at.is_some() {
+                        return Err(ApiError::BadRequest(String::from("账号已停用")));
+                    }
+                    let user = diesel::update(users::table.find(user_id))
+                        .set((
+                            users::disabled_at.eq(diesel::dsl::now),
+                            users::token_generation.eq(users::token_generation + 1),
+                            users::updated_at.eq(diesel::dsl::now),
+                        ))
+                        .get_result::<Users>(x)?;
+                    diesel::update(
+                        refresh_tokens::table
+                            .filter(refresh_tokens::user_id.eq(user_id))
+                            .filter(refresh_tokens::revoked_at.is_null()),
+                    )
+                    .set((
+                        refresh_tokens::revoked_at.eq(diesel::dsl::now),
+                        refresh_tokens::updated_at.eq(diesel::dsl::now),
+                    ))
+                    .execute(x)?;
+                    AdminAuditLogs::create(
+                        x,
+                        admin_id,
+                        AdminAction::DisableUser,
+                        Some(user_id),
+                        json!({ "reason": reason }),
+                    )?;
+                    Ok(user)
+                })
+            })
+            .await?;
+        RevokedTokens::forget(user_id);
+        Ok(user)
+    }
+
+    pub async fn enable_user(
+        &self,
+        conn: &BpRecordConn,
+        user_id: Uuid,
+        reason: Option<String>,
+    ) -> Result<Users, ApiError> {
+        let admin_id = self.0;
+        let user = conn
+            .run(move |c| {
+                c.transaction(|x| {
+                    if find_user(x, user_id)?.disabled_at.is_none() {
+                        return Err(ApiError::BadRequest(String::from("账号未停用")));
+                    }
+                    let user = diesel::update(users::table.find(user_id))
+                        .set((
+                            users::disabled_at.eq(None::<NaiveDateTime>),
+                            users::updated_at.eq(diesel::dsl::now),
+                        ))
+                        .get_result::<Users>(x)?;
+                    AdminAuditLogs::create(
+                        x,
+                        admin_id,
+                        AdminAction::EnableUser,
+                        Some(user_id),
+                        json!({ "reason": reason }),
+                    )?;
+                    Ok(user)
+                })
+            })
+            .await?;
+        RevokedTokens::restore(user_id, user.token_generation, user.role.clone());
+        Ok(user)
+    }
+
+    /// Replaces the user's plan and member limit override; `null` falls back
+    /// to the default plan.
+    pub async fn update_quota(
+        &self,
+        conn: &BpRecordConn,
+        user_id: Uuid,
+        quota: QuotaUpdate,
+    ) -> Result<MemberQuota, ApiError> {
+        let admin_id = self.0;
+        if quota
+            .member_limit
+            .is_some_and(|member_limit| member_limit < 0)
+        {
+            return Err(ApiError::BadRequest(String::from("成员上限不能为负数")));
+        }
+        let member_quota = conn
+            .run(move |c| {
+                c.transaction(|x| {
+                    find_user(x, user_id)?;
+                    if let Some(plan_id) = quota.plan_id {
+                        let exists =
+                            diesel::select(diesel::dsl::exists(quota_plans::table.find(plan_id)))
+                                .get_result::<bool>(x)?;
+                        if !exists {
+                            return Err(ApiError::BadRequest(String::from("套餐不存在")));
+                        }
+                    }
+                    let before = user_quotas::table
+                        .find(user_id)
+                        .select((user_quotas::plan_id, user_quotas::member_limit))
+                        .get_result::<(Option<Uuid>, Option<i32>)>(x)
+                        .optional()?
+                        .unwrap_or_default();
+                    diesel::insert_into(user_quotas::table)
+                        .values((
+                            user_quotas::user_id.eq(user_id),
+                            user_quotas::plan_id.eq(quota.plan_id),
+                            user_quotas::member_limit.eq(quota.member_limit),
+                        ))
+                        .on_conflict(user_quotas::user_id)
+                        .do_update()
+                        .set((
+                            user_quotas::plan_id.eq(quota.plan_id),
+                            user_quotas::member_limit.eq(quota.member_limit),
+                            user_quotas::updated_at.eq(diesel::dsl::now),
+                        ))
+                        .execute(x)?;
+                    AdminAuditLogs::create(
+                        x,
+                        admin_id,
+                        AdminAction::UpdateQuota,
+                        Some(user_id),
+                        json!({
+                            "before": { "plan_id": before.0, "member_limit": before.1 },
+                            "after": { "plan_id": quota.plan_id, "member_limit": quota.member_limit },
+                        }),
+                    )?;
+                    Ok::<MemberQuota, ApiError>(MemberQuota::load(x, user_id)?)
+                })
+            })
+            .await?;
+        Ok(member_quota)
+    }
+
+    /// Merges two members owned by `user_id`. Returns the report and the
+    /// source's avatar key, which the caller removes from storage.
+    pub async fn merge_members(
+        &self,
+        conn: &BpRecordConn,
+        user_id: Uuid,
+        target_id: Uuid,
+        source_id: Uuid,
+    ) -> Result<(MergeReport, Option<String>), ApiError> {
+        let admin_id = self.0;
+        let result = conn
+            .run(move |c| {
+                c.transaction(|x| {
+                    let avatar_key = members::table
+                        .find(source_id)
+                        .select(members::avatar_key)
+                        .get_result::<Option<String>>(x)
+                        .optional()?
+                        .ok_or(ApiError::NotFound)?;
+                    let report = Members::merge(x, user_id, target_id, source_id)?;
+                    AdminAuditLogs::create(
+                        x,
+                        admin_id,
+                        AdminAction::MergeMembers,
+                        Some(user_id),
+                        json!({
+                            "target_id": target_id,
+                            "source_id": source_id,
+                            "moved_records": report.moved_records,
+                            "removed_duplicates": report.removed_duplicates,
+                            "removed_shares": report.removed_shares,
+                        }),
+                    )?;
+                    Ok::<(MergeReport, Option<String>), ApiError>((report, avatar_key))
+                })
+            })
+            .await?;
+        Ok(result)
+    }
+}
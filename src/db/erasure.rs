@@ -0,0 +1,365 @@
+use crate::db::BpRecordConn;
+use crate::db::member::MemberRole;
+use crate::error::api::ApiError;
+use crate::identity::PHONE;
+use crate::schema::{
+    account_deletions, account_erasures, data_exports, login_events, member_invitations, members,
+    records, records_archive, refresh_tokens, revoked_tokens, sms_codes, user_identities,
+    user_member, user_profiles, user_quotas, users,
+};
+use crate::util::secret::{hash_secret, random_code};
+use crate::util::serde_time_format;
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::{PgConnection, Queryable, Selectable};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::env;
+use uuid::Uuid;
+
+lazy_static! {
+    pub static ref ACCOUNT_DELETION_CONFIRM_EXPIRE: i64 = {
+        env::var("ACCOUNT_DELETION_CONFIRM_EXPIRE")
+            .unwrap_or_else(|_| "600".to_owned())
+            .parse::<i64>()
+            .unwrap()
+    };
+    pub static ref ACCOUNT_DELETION_GRACE: i64 = {
+        env::var("ACCOUNT_DELETION_GRACE")
+            .unwrap_or_else(|_| "604800".to_owned())
+            .parse::<i64>()
+            .unwrap()
+    };
+}
+
+const CODE_LENGTH: usize = 8;
+
+#[derive(Debug, Serialize, Identifiable, Queryable, Selectable)]
+#[diesel(
+    table_name = crate::schema::account_deletions,
+    primary_key(user_id),
+    check_for_backend(diesel::pg::Pg),
+)]
+pub struct AccountDeletions {
+    pub user_id: Uuid,
+    #[serde(skip_serializing)]
+    pub code_hash: String,
+    #[serde(skip_serializing)]
+    pub code_expires_at: NaiveDateTime,
+    #[serde(with = "serde_time_format::optional")]
+    pub erase_at: Option<NaiveDateTime>,
+    #[serde(with = "serde_time_format")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "serde_time_format")]
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Identifiable, Queryable, Selectable)]
+#[diesel(
+    table_name = crate::schema::account_erasures,
+    primary_key(id),
+    check_for_backend(diesel::pg::Pg),
+)]
+pub struct AccountErasures {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    #[serde(with = "serde_time_format")]
+    pub requested_at: NaiveDateTime,
+    pub member_num: i32,
+    pub record_num: i32,
+    #[serde(with = "serde_time_format")]
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize)]
+pub struct DeletionConfirmation {
+    pub confirmation: String,
+    pub expires_in: i64,
+}
+
+pub struct Erasure {
+    pub audit: AccountErasures,
+    /// Avatars of erased members and the user's data exports, to be removed
+    /// from storage once the transaction has committed.
+    pub avatar_keys: Vec<String>,
+    pub export_keys: Vec<String>,
+}
+
+impl AccountDeletions {
+    /// Issues the code that `confirm` expects, so a single stray request can
+    /// not schedule a deletion.
+    pub async fn request(
+        conn: &BpRecordConn,
+        user_id: Uuid,
+    ) -> Result<DeletionConfirmation, ApiError> {
+        let code = random_code(CODE_LENGTH);
+        let code_hash = hash_secret(&code);
+        let code_expires_at =
+            Utc::now().naive_utc() + Duration::seconds(*ACCOUNT_DELETION_CONFIRM_EXPIRE);
+        let requested = conn
+            .run(move |c| {
+                c.transaction(|x| {
+                    let scheduled = diesel::select(diesel::dsl::exists(
+                        account_deletions::table
+                            .find(user_id)
+                            .filter(account_deletions::erase_at.is_not_null()),
+                    ))
+                    .get_result::<bool>(x)?;
+                    if scheduled {
+                        return Ok(false);
+                    }
+                    diesel::insert_into(account_deletions::table)
+                        .values((
+                            account_deletions::user_id.eq(user_id),
+                            account_deletions::code_hash.eq(&code_hash),
+                            account_deletions::code_expires_at.eq(code_expires_at),
+                        ))
+                        .on_conflict(account_deletions::user_id)
+                        .do_update()
+                        .set((
+                            account_deletions::code_hash.eq(&code_hash),
+                            account_deletions::code_expires_at.eq(code_expires_at),
+                            account_deletions::updated_at.eq(diesel::dsl::now),
+                        ))
+                        .execute(x)?;
+                    Ok::<bool, diesel::result::Error>(true)
+                })
+            })
+            .await?;
+        if !requested {
+            return Err(ApiError::BadRequest(String::from("账号已在注销中")));
+        }
+        Ok(DeletionConfirmation {
+            confirmation: code,
+            expires_in: *ACCOUNT_DELETION_CONFIRM_EXPIRE,
+        })
+    }
+
+    /// Schedules the erasure `ACCOUNT_DELETION_GRACE` seconds from now.
+    pub async fn confirm(
+        conn: &BpRecordConn,
+        user_id: Uuid,
+        code: String,
+    ) -> Result<AccountDeletions, ApiError> {
+        let code_hash = hash_secret(code.trim());
+        let erase_at = Utc::now().naive_utc() + Duration::seconds(*ACCOUNT_DELETION_GRACE);
+        let deletion = conn
+            .run(move |c| {
+                diesel::update(
+                    account_deletions::table
+                        .find(user_id)
+                        .filter(account_deletions::code_hash.eq(code_hash))
+                        .filter(account_deletions::code_expires_at.gt(diesel::dsl::now))
+                        .filter(account_deletions::erase_at.is_null()),
+                )
+                .set((
+                    account_deletions::erase_at.eq(erase_at),
+                    account_deletions::updated_at.eq(diesel::dsl::now),
+                ))
+                .get_result::<AccountDeletions>(c)
+                .optional()
+            })
+            .await?;
+        deletion.ok_or_else(|| ApiError::BadRequest(String::from("确认码错误或已失效")))
+    }
+
+    pub async fn status(
+        conn: &BpRecordConn,
+        user_id: Uuid,
+    ) -> Result<Option<AccountDeletions>, ApiError> {
+        let deletion = conn
+            .run(move |c| {
+                account_deletions::table
+                    .find(user_id)
+                    .filter(account_deletions::erase_at.is_not_null())
+                    .get_result::<AccountDeletions>(c)
+                    .optional()
+            })
+            .await?;
+        Ok(deletion)
+    }
+
+    pub async fn cancel(conn: &BpRecordConn, user_id: Uuid) -> Result<usize, ApiError> {
+        let num = conn
+            .run(move |c| diesel::delete(account_deletions::table.find(user_id)).execute(c))
+            .await?;
+        Ok(num)
+    }
+
+    /// Users whose grace period is over.
+    pub async fn due(conn: &BpRecordConn) -> Result<Vec<Uuid>, ApiError> {
+        let user_ids = conn
+            .run(move |c| {
+                account_deletions::table
+                    .filter(account_deletions::erase_at.le(diesel::dsl::now))
+                    .select(account_deletions::user_id)
+                    .get_results::<Uuid>(c)
+            })
+            .await?;
+        Ok(user_ids)
+    }
+}
+
+impl AccountErasures {
+    /// Removes the user with their identities, tokens, exports and memberships.
+    /// Members shared with other users are kept, and ownership passes to the
+    /// longest-linked remaining user if nobody else owns them.
+    pub async fn erase(conn: &BpRecordConn, user_id: Uuid) -> Result<Option<Erasure>, ApiError> {
+        let erasure = conn
+            .run(move |c| {
+                c.transaction(|x| {
+                    let deletion = account_deletions::table
+                        .find(user_id)
+                        .filter(account_deletions::erase_at.le(diesel::dsl::now))
+                        .for_update()
+                        .get_result::<AccountDeletions>(x)
+                        .optional()?;
+                    let Some(deletion) = deletion else {
+                        return Ok(None);
+                    };
+                    let member_ids = user_member::table
+                        .filter(user_member::user_id.eq(user_id))
+                        .select(user_member::member_id)
+                        .get_results::<Uuid>(x)?;
+                    let shared_ids = user_member::table
+                        .filter(user_member::member_id.eq_any(&member_ids))
+                        .filter(user_member::user_id.ne(user_id))
+                        .select(user_member::member_id)
+                        .distinct()
+                        .get_results::<Uuid>(x)?;
+                    let owned_ids = member_ids
+                        .into_iter()
+                        .filter(|member_id| !shared_ids.contains(member_id))
+                        .collect::<Vec<_>>();
+                    for member_id in &shared_ids {
+                        AccountErasures::hand_over(x, user_id, *member_id)?;
+                    }
+
+                    let avatar_keys = members::table
+                        .filter(members::id.eq_any(&owned_ids))
+                        .filter(members::avatar_key.is_not_null())
+                        .select(members::avatar_key.assume_not_null())
+                        .get_results::<String>(x)?;
+                    let record_num = diesel::delete(
+                        records::table.filter(records::member_id.eq_any(&owned_ids)),
+                    )
+                    .execute(x)?
+                        + diesel::delete(
+                            records_archive::table
+                                .filter(records_archive::member_id.eq_any(&owned_ids)),
+                        )
+                        .execute(x)?;
+                    diesel::delete(
+                        member_invitations::table.filter(
+                            member_invitations::member_id
+                                .eq_any(&owned_ids)
+                                .or(member_invitations::inviter_id.eq(user_id)),
+                        ),
+                    )
+                    .execute(x)?;
+                    diesel::update(
+                        member_invitations::table
+                            .filter(member_invitations::accepted_by.eq(user_id)),
+                    )
+                    .set(member_invitations::accepted_by.eq(None::<Uuid>))
+                    .execute(x)?;
+                    diesel::delete(user_member::table.filter(user_member::user_id.eq(user_id)))
+                        .execute(x)?;
+                    let member_num =
+                        diesel::delete(members::table.filter(members::id.eq_any(&owned_ids)))
+                            .execute(x)?;
+
+                    let phones = user_identities::table
+                        .filter(user_identities::user_id.eq(user_id))
+                        .filter(user_identities::provider.eq(PHONE))
+                        .select(user_identities::subject)
+                        .get_results::<String>(x)?
+                        .into_iter()
+                        .chain(
+                            users::table
+                                .find(user_id)
+                                .select(users::phone)
+                                .get_result::<Option<String>>(x)?,
+                        )
+                        .collect::<Vec<_>>();
+                    diesel::delete(sms_codes::table.filter(sms_codes::phone.eq_any(phones)))
+                        .execute(x)?;
+                    diesel::delete(
+                        user_identities::table.filter(user_identities::user_id.eq(user_id)),
+                    )
+                    .execute(x)?;
+                    diesel::delete(
+                        refresh_tokens::table.filter(refresh_tokens::user_id.eq(user_id)),
+                    )
+                    .execute(x)?;
+                    diesel::delete(
+                        revoked_tokens::table.filter(revoked_tokens::user_id.eq(user_id)),
+                    )
+                    .execute(x)?;
+                    diesel::delete(login_events::table.filter(login_events::user_id.eq(user_id)))
+                        .execute(x)?;
+                    let export_keys = diesel::delete(
+                        data_exports::table.filter(data_exports::user_id.eq(user_id)),
+                    )
+                    .returning(data_exports::storage_key)
+                    .get_results::<Option<String>>(x)?
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                    diesel::delete(user_profiles::table.find(user_id)).execute(x)?;
+                    diesel::delete(user_quotas::table.find(user_id)).execute(x)?;
+                    diesel::delete(account_deletions::table.find(user_id)).execute(x)?;
+                    diesel::delete(users::table.find(user_id)).execute(x)?;
+
+                    let audit = diesel::insert_into(account_erasures::table)
+                        .values((
+                            account_erasures::user_id.eq(user_id),
+                            account_erasures::requested_at.eq(deletion.updated_at),
+                            account_erasures::member_num.eq(member_num as i32),
+                            account_erasures::record_num.eq(record_num as i32),
+                        ))
+                        .get_result::<AccountErasures>(x)?;
+                    Ok::<Option<Erasure>, diesel::result::Error>(Some(Erasure {
+                        audit,
+                        avatar_keys,
+                        export_keys,
+                    }))
+                })
+            })
+            .await?;
+        Ok(erasure)
+    }
+
+    fn hand_over(x: &mut PgConnection, user_id: Uuid, member_id: Uuid) -> QueryResult<()> {
+        let owner = MemberRole::Owner.as_str();
+        let other_owner = diesel::select(diesel::dsl::exists(
+            user_member::table
+                .filter(user_member::member_id.eq(member_id))
+                .filter(user_member::user_id.ne(user_id))
+                .filter(user_member::role.eq(owner)),
+        ))
+        .get_result::<bool>(x)?;
+        let is_owner = diesel::select(diesel::dsl::exists(
+            user_member::table
+                .find((user_id, member_id))
+                .filter(user_member::role.eq(owner)),
+        ))
+        .get_result::<bool>(x)?;
+        if other_owner || !is_owner {
+            return Ok(());
+        }
+        let successor = user_member::table
+            .filter(user_member::member_id.eq(member_id))
+            .filter(user_member::user_id.ne(user_id))
+            .order(user_member::created_at.asc())
+            .select(user_member::user_id)
+            .first::<Uuid>(x)?;
+        diesel::update(user_member::table.find((successor, member_id)))
+            .set((
+                user_member::role.eq(owner),
+                user_member::updated_at.eq(diesel::dsl::now),
+            ))
+            .execute(x)?;
+        Ok(())
+    }
+}
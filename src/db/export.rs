@@ -0,0 +1,175 @@
+use crate::db::BpRecordConn;
+use crate::error::api::ApiError;
+use crate::schema::data_exports;
+use crate::util::secret::{hash_secret, random_token};
+use crate::util::serde_time_format;
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::{Queryable, Selectable};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::env;
+use uuid::Uuid;
+
+lazy_static! {
+    pub static ref EXPORT_EXPIRE: i64 = {
+        env::var("EXPORT_EXPIRE")
+            .unwrap_or_else(|_| "86400".to_owned())
+            .parse::<i64>()
+            .unwrap()
+    };
+}
+
+const TOKEN_LENGTH: usize = 48;
+
+pub const PENDING: &str = "pending";
+pub const READY: &str = "ready";
+pub const FAILED: &str = "failed";
+
+#[derive(Debug, Serialize, Identifiable, Queryable, Selectable)]
+#[diesel(
+    table_name = crate::schema::data_exports,
+    primary_key(id),
+    check_for_backend(diesel::pg::Pg),
+)]
+pub struct DataExports {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub status: String,
+    #[serde(skip_serializing)]
+    pub storage_key: Option<String>,
+    #[serde(with = "serde_time_format")]
+    pub expires_at: NaiveDateTime,
+    #[serde(with = "serde_time_format")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "serde_time_format")]
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Serialize)]
+pub struct ExportTicket {
+    pub download_token: String,
+    pub export: DataExports,
+}
+
+impl DataExports {
+    /// Starts a new export unless one is still being built for the user.
+    pub async fn insert(conn: &BpRecordConn, user_id: Uuid) -> Result<ExportTicket, ApiError> {
+        let token = random_token(TOKEN_LENGTH);
+        let token_hash = hash_secret(&token);
+        let expires_at = Utc::now().naive_utc() + Duration::seconds(*EXPORT_EXPIRE);
+        let export = conn
+            .run(move |c| {
+                c.transaction(|x| {
+                    let pending = diesel::select(diesel::dsl::exists(
+                        data_exports::table
+                            .filter(data_exports::user_id.eq(user_id))
+                            .filter(data_exports::status.eq(PENDING))
+                            .filter(data_exports::expires_at.gt(diesel::dsl::now)),
+                    ))
+                    .get_result::<bool>(x)?;
+                    if pending {
+                        return Ok(None);
+                    }
+                    diesel::insert_into(data_exports::table)
+                        .values((
+                            data_exports::user_id.eq(user_id),
+                            data_exports::token_hash.eq(token_hash),
+                            data_exports::expires_at.eq(expires_at),
+                        ))
+                        .get_result::<DataExports>(x)
+                        .map(Some)
+                })
+            })
+            .await?;
+        let export =
+            export.ok_or_else(|| ApiError::BadRequest(String::from("数据导出正在进行中")))?;
+        Ok(ExportTicket {
+            download_token: token,
+            export,
+        })
+    }
+
+    pub async fn detail(
+        conn: &BpRecordConn,
+        user_id: Uuid,
+        id: Uuid,
+    ) -> Result<DataExports, ApiError> {
+        let export = conn
+            .run(move |c| {
+                data_exports::table
+                    .find(id)
+                    .filter(data_exports::user_id.eq(user_id))
+                    .get_result::<DataExports>(c)
+            })
+            .await?;
+        Ok(export)
+    }
+
+    pub async fn find_ready(conn: &BpRecordConn, token: String) -> Result<DataExports, ApiError> {
+        let token_hash = hash_secret(token.trim());
+        let export = conn
+            .run(move |c| {
+                data_exports::table
+                    .filter(data_exports::token_hash.eq(token_hash))
+                    .filter(data_exports::status.eq(READY))
+                    .filter(data_exports::expires_at.gt(diesel::dsl::now))
+                    .get_result::<DataExports>(c)
+                    .optional()
+            })
+            .await?;
+        export.ok_or_else(|| ApiError::BadRequest(String::from("下载链接无效或已过期")))
+    }
+
+    pub async fn finish(
+        conn: &BpRecordConn,
+        id: Uuid,
+        storage_key: Option<String>,
+    ) -> Result<DataExports, ApiError> {
+        let status = if storage_key.is_some() { READY } else { FAILED };
+        let export = conn
+            .run(move |c| {
+                diesel::update(data_exports::table.find(id))
+                    .set((
+                        data_exports::status.eq(status),
+                        data_exports::storage_key.eq(storage_key),
+                        data_exports::updated_at.eq(diesel::dsl::now),
+                    ))
+                    .get_result::<DataExports>(c)
+            })
+            .await?;
+        Ok(export)
+    }
+
+    /// Marks every `pending` export as failed. Only safe at startup, before
+    /// any export task of this process is running.
+    pub async fn fail_pending(conn: &BpRecordConn) -> Result<usize, ApiError> {
+        let count = conn
+            .run(|c| {
+                diesel::update(data_exports::table.filter(data_exports::status.eq(PENDING)))
+                    .set((
+                        data_exports::status.eq(FAILED),
+                        data_exports::updated_at.eq(diesel::dsl::now),
+                    ))
+                    .execute(c)
+            })
+            .await?;
+        Ok(count)
+    }
+
+    /// Deletes every expired export and returns their storage keys.
+    pub async fn purge_expired(conn: &BpRecordConn) -> Result<Vec<String>, ApiError> {
+        let storage_keys = conn
+            .run(|c| {
+                diesel::delete(
+                    data_exports::table.filter(data_exports::expires_at.le(diesel::dsl::now)),
+                )
+                .returning(data_exports::storage_key)
+                .get_results::<Option<String>>(c)
+            })
+            .await?;
+        Ok(storage_keys.into_iter().flatten().collect())
+    }
+}
@@ -0,0 +1,336 @@
+use crate::db::BpRecordConn;
+use crate::db::member::{MemberRole, UserMember};
+use crate::db::revocation::RevokedTokens;
+use crate::db::user::{Users, seal_session_key};
+use crate::error::api::ApiError;
+use crate::identity::Identity;
+use crate::schema::{user_identities, user_member, users};
+use crate::util::serde_time_format;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind;
+use diesel::result::Error::DatabaseError;
+use diesel::{PgConnection, Queryable, Selectable};
+use serde::Serialize;
+use std::str::FromStr;
+use uuid::Uuid;
+
+const SIGN_IN_ATTEMPTS: usize = 3;
+
+#[derive(Debug, Serialize, Identifiable, Queryable, Selectable, Associations)]
+#[diesel(
+    table_name = crate::schema::user_identities,
+    primary_key(id),
+    belongs_to(Users, foreign_key = user_id),
+    check_for_backend(diesel::pg::Pg),
+)]
+pub struct UserIdentities {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub provider: String,
+    #[serde(skip_serializing)]
+    pub subject: String,
+    #[serde(skip_serializing)]
+    pub credential: Option<String>,
+    #[serde(with = "serde_time_format")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "serde_time_format")]
+    pub updated_at: NaiveDateTime,
+}
+
+impl UserIdentities {
+    pub async fn find(
+        conn: &BpRecordConn,
+        provider: &'static str,
+        subject: String,
+    ) -> Result<Option<UserIdentities>, ApiError> {
+        let identity = conn
+            .run(move |c| {
+                user_identities::table
+                    .filter(user_identities::provider.eq(provider))
+                    .filter(user_identities::subject.eq(subject))
+                    .get_result::<UserIdentities>(c)
+                    .optional()
+            })
+            .await?;
+        Ok(identity)
+    }
+
+    pub async fn get_user_identities(
+        conn: &BpRecordConn,
+        user_id: Uuid,
+    ) -> Result<Vec<UserIdentities>, ApiError> {
+        let identity_list = conn
+            .run(move |c| {
+                user_identities::table
+                    .filter(user_identities::user_id.eq(user_id))
+                    .order(user_identities::created_at.asc())
+                    .get_results::<UserIdentities>(c)
+            })
+            .await?;
+        Ok(identity_list)
+    }
+
+    /// Returns the user owning `identity`, creating both on first sign-in.
+    /// A known WeChat unionid takes precedence over the openid, so signing in
+    /// from another app of the same open platform links to the same user.
+    /// If the openid is still linked to a different user, that user is merged
+    /// into the unionid's one.
+    pub async fn sign_in(conn: &BpRecordConn, identity: Identity) -> Result<Users, ApiError> {
+        let identity = sealed(identity)?;
+        let (user, merged) = conn
+            .run(move |c| {
+                // A concurrent first sign-in may create the user for the same
+                // unionid or openid first; the retry then finds it.
+                let mut attempt = 1;
+                loop {
+                    match c.transaction(|x| UserIdentities::resolve(x, &identity)) {
+                        Err(DatabaseError(DatabaseErrorKind::UniqueViolation, _))
+                            if attempt < SIGN_IN_ATTEMPTS =>
+                        {
+                            attempt += 1;
+                        }
+                        result => break result,
+                    }
+                }
+            })
+            .await?;
+        if let Some(merged) = merged {
+            RevokedTokens::forget(merged);
+        }
+        Ok(user)
+    }
+
+    /// Finds or creates the user of `identity`. Also returns the user merged
+    /// away, if any.
+    fn resolve(x: &mut PgConnection, identity: &Identity) -> QueryResult<(Users, Option<Uuid>)> {
+        let union_user = match &identity.unionid {
+            Some(unionid) => users::table
+                .filter(users::unionid.eq(unionid))
+                .select(users::id)
+                .get_result::<Uuid>(x)
+                .optional()?,
+            None => None,
+        };
+        let linked = user_identities::table
+            .filter(user_identities::provider.eq(identity.provider))
+            .filter(user_identities::subject.eq(&identity.subject))
+            .get_result::<UserIdentities>(x)
+            .optional()?;
+        let mut merged = None;
+        let user_id = match (union_user, linked) {
+            (Some(user_id), Some(linked)) => {
+                if linked.user_id != user_id {
+                    info!(
+                        "{} identity {} belongs to user {}, unionid to user {}; merging",
+                        identity.provider, linked.id, linked.user_id, user_id
+                    );
+                    merge_user(x, user_id, linked.user_id)?;
+                    merged = Some(linked.user_id);
+                }
+                user_id
+            }
+            (Some(user_id), None) => {
+                UserIdentities::create(x, user_id, identity)?;
+                user_id
+            }
+            (None, Some(linked)) => linked.user_id,
+            (None, None) => {
+                let user = diesel::insert_into(users::table)
+                    .values(users::unionid.eq(&identity.unionid))
+                    .get_result::<Users>(x)?;
+                UserIdentities::create(x, user.id, identity)?;
+                user.id
+            }
+        };
+        if let Some(unionid) = &identity.unionid {
+            diesel::update(users::table.find(user_id).filter(users::unionid.is_null()))
+                .set(users::unionid.eq(unionid))
+                .execute(x)?;
+        }
+        let user = match &identity.session_key {
+            Some(session_key) => diesel::update(users::table.find(user_id))
+                .set((
+                    users::session_key.eq(session_key),
+                    users::updated_at.eq(diesel::dsl::now),
+                ))
+                .get_result::<Users>(x)?,
+            None => users::table.find(user_id).get_result::<Users>(x)?,
+        };
+        Ok((user, merged))
+    }
+
+    pub async fn register(conn: &BpRecordConn, identity: Identity) -> Result<Users, ApiError> {
+        let identity = sealed(identity)?;
+        let user = conn
+            .run(move |c| {
+                c.transaction(|x| {
+                    let user = diesel::insert_into(users::table)
+                        .values((
+                            users::session_key.eq(&identity.session_key),
+                            users::unionid.eq(&identity.unionid),
+                        ))
+                        .get_result::<Users>(x)?;
+                    UserIdentities::create(x, user.id, &identity)?;
+                    Ok::<Users, diesel::result::Error>(user)
+                })
+            })
+            .await;
+        match user {
+            Err(DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+                Err(ApiError::BadRequest(String::from("该账号已注册")))
+            }
+            user => Ok(user?),
+        }
+    }
+
+    pub async fn link(
+        conn: &BpRecordConn,
+        user_id: Uuid,
+        identity: Identity,
+    ) -> Result<UserIdentities, ApiError> {
+        let identity = sealed(identity)?;
+        let linked = conn
+            .run(move |c| {
+                c.transaction(|x| {
+                    if let Some(unionid) = &identity.unionid {
+                        let num = diesel::update(
+                            users::table
+                                .find(user_id)
+                                .filter(users::unionid.is_null().or(users::unionid.eq(unionid))),
+                        )
+                        .set(users::unionid.eq(unionid))
+                        .execute(x)?;
+                        if num == 0 {
+                            return Ok(None);
+                        }
+                    }
+                    if let Some(session_key) = &identity.session_key {
+                        diesel::update(users::table.find(user_id))
+                            .set((
+                                users::session_key.eq(session_key),
+                                users::updated_at.eq(diesel::dsl::now),
+                            ))
+                            .execute(x)?;
+                    }
+                    let linked = UserIdentities::create(x, user_id, &identity)?;
+                    Ok::<Option<UserIdentities>, diesel::result::Error>(Some(linked))
+                })
+            })
+            .await;
+        match linked {
+            Ok(Some(linked)) => Ok(linked),
+            // The account already belongs to another unionid, or another user
+            // holds this identity or unionid.
+            Ok(None) | Err(DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+                Err(ApiError::BadRequest(String::from("该身份已绑定账号")))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub async fn unlink(conn: &BpRecordConn, user_id: Uuid, id: Uuid) -> Result<(), ApiError> {
+        conn.run(move |c| {
+            c.transaction(|x| {
+                let identity_num = user_identities::table
+                    .filter(user_identities::user_id.eq(user_id))
+                    .count()
+                    .get_result::<i64>(x)?;
+                if identity_num <= 1 {
+                    return Err(ApiError::BadRequest(String::from("至少保留一种登录方式")));
+                }
+                let num = diesel::delete(
+                    user_identities::table
+                        .find(id)
+                        .filter(user_identities::user_id.eq(user_id)),
+                )
+                .execute(x)?;
+                if num == 0 {
+                    return Err(ApiError::NotFound);
+                }
+                Ok::<(), ApiError>(())
+            })
+        })
+        .await
+    }
+
+    fn create(
+        x: &mut PgConnection,
+        user_id: Uuid,
+        identity: &Identity,
+    ) -> QueryResult<UserIdentities> {
+        diesel::insert_into(user_identities::table)
+            .values((
+                user_identities::user_id.eq(user_id),
+                user_identities::provider.eq(identity.provider),
+                user_identities::subject.eq(&identity.subject),
+                user_identities::credential.eq(&identity.credential),
+            ))
+            .get_result::<UserIdentities>(x)
+    }
+}
+
+/// Moves the identities and members of `source_id` to `target_id` and
+/// disables `source_id`, whose tokens stop working. Where both users share a
+/// member, the higher of the two roles is kept.
+fn merge_user(x: &mut PgConnection, target_id: Uuid, source_id: Uuid) -> QueryResult<()> {
+    diesel::update(user_identities::table.filter(user_identities::user_id.eq(source_id)))
+        .set((
+            user_identities::user_id.eq(target_id),
+            user_identities::updated_at.eq(diesel::dsl::now),
+        ))
+        .execute(x)?;
+    let source_links = user_member::table
+        .filter(user_member::user_id.eq(source_id))
+        .get_results::<UserMember>(x)?;
+    for source_link in source_links {
+        let target_role = user_member::table
+            .find((target_id, source_link.member_id))
+            .select(user_member::role)
+            .get_result::<String>(x)
+            .optional()?;
+        match target_role {
+            Some(target_role) => {
+                if role_rank(&source_link.role) > role_rank(&target_role) {
+                    diesel::update(user_member::table.find((target_id, source_link.member_id)))
+                        .set((
+                            user_member::role.eq(&source_link.role),
+                            user_member::updated_at.eq(diesel::dsl::now),
+                        ))
+                        .execute(x)?;
+                }
+                diesel::delete(user_member::table.find((source_id, source_link.member_id)))
+                    .execute(x)?;
+            }
+            None => {
+                diesel::update(user_member::table.find((source_id, source_link.member_id)))
+                    .set((
+                        user_member::user_id.eq(target_id),
+                        user_member::updated_at.eq(diesel::dsl::now),
+                    ))
+                    .execute(x)?;
+            }
+        }
+    }
+    diesel::update(users::table.find(source_id))
+        .set((
+            users::unionid.eq(None::<String>),
+            users::session_key.eq(None::<String>),
+            users::disabled_at.eq(diesel::dsl::now),
+            users::token_generation.eq(users::token_generation + 1),
+            users::updated_at.eq(diesel::dsl::now),
+        ))
+        .execute(x)?;
+    Ok(())
+}
+
+fn role_rank(role: &str) -> Option<MemberRole> {
+    MemberRole::from_str(role).ok()
+}
+
+fn sealed(identity: Identity) -> Result<Identity, ApiError> {
+    Ok(Identity {
+        session_key: seal_session_key(identity.session_key)?,
+        ..identity
+    })
+}
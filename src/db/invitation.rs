@@ -0,0 +1,277 @@
+use crate::db::BpRecordConn;
+use crate::db::member::{MemberRole, UserMember};
+use crate::db::quota::MemberQuota;
+use crate::error::api::ApiError;
+use crate::schema::{member_invitations, user_member};
+use crate::util::secret::{hash_secret, random_code};
+use crate::util::serde_time_format;
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::{PgConnection, Queryable, Selectable};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::env;
+use uuid::Uuid;
+
+lazy_static! {
+    pub static ref INVITATION_EXPIRE: i64 = {
+        env::var("INVITATION_EXPIRE")
+            .unwrap_or_else(|_| "86400".to_owned())
+            .parse::<i64>()
+            .unwrap()
+    };
+}
+
+const CODE_LENGTH: usize = 8;
+
+#[derive(Debug, Serialize, Identifiable, Queryable, Selectable)]
+#[diesel(
+    table_name = crate::schema::member_invitations,
+    primary_key(id),
+    check_for_backend(diesel::pg::Pg),
+)]
+pub struct MemberInvitations {
+    pub id: Uuid,
+    pub member_id: Uuid,
+    pub inviter_id: Uuid,
+    #[serde(skip_serializing)]
+    pub code_hash: String,
+    pub role: String,
+    #[serde(with = "serde_time_format")]
+    pub expires_at: NaiveDateTime,
+    pub accepted_by: Option<Uuid>,
+    #[serde(with = "serde_time_format::optional")]
+    pub accepted_at: Option<NaiveDateTime>,
+    #[serde(with = "serde_time_format::optional")]
+    pub revoked_at: Option<NaiveDateTime>,
+    #[serde(with = "serde_time_format")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "serde_time_format")]
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Deserialize)]
+pub struct NewInvitation {
+    pub member_id: Uuid,
+    pub role: MemberRole,
+    pub expires_in: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct NewTransfer {
+    pub member_id: Uuid,
+    pub expires_in: Option<i64>,
+}
+
+impl From<NewTransfer> for NewInvitation {
+    fn from(new_transfer: NewTransfer) -> Self {
+        NewInvitation {
+            member_id: new_transfer.member_id,
+            role: MemberRole::Owner,
+            expires_in: new_transfer.expires_in,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct InvitationCode {
+    pub code: String,
+    pub invitation: MemberInvitations,
+}
+
+impl MemberInvitations {
+    pub async fn insert(
+        conn: &BpRecordConn,
+        inviter_id: Uuid,
+        new_invitation: NewInvitation,
+    ) -> Result<InvitationCode, ApiError> {
+        let expires_in = new_invitation
+            .expires_in
+            .unwrap_or(*INVITATION_EXPIRE)
+            .clamp(60, *INVITATION_EXPIRE);
+        let expires_at = Utc::now().naive_utc() + Duration::seconds(expires_in);
+        let code = random_code(CODE_LENGTH);
+        let code_hash = hash_secret(&code);
+        let invitation = conn
+            .run(move |c| {
+                diesel::insert_into(member_invitations::table)
+                    .values((
+                        member_invitations::member_id.eq(new_invitation.member_id),
+                        member_invitations::inviter_id.eq(inviter_id),
+                        member_invitations::code_hash.eq(code_hash),
+                        member_invitations::role.eq(new_invitation.role.as_str()),
+                        member_invitations::expires_at.eq(expires_at),
+                    ))
+                    .get_result::<MemberInvitations>(c)
+            })
+            .await?;
+        Ok(InvitationCode { code, invitation })
+    }
+
+    pub async fn detail(conn: &BpRecordConn, id: Uuid) -> Result<MemberInvitations, ApiError> {
+        let invitation = conn
+            .run(move |c| {
+                member_invitations::table
+                    .find(id)
+                    .get_result::<MemberInvitations>(c)
+            })
+            .await?;
+        Ok(invitation)
+    }
+
+    pub async fn get_pending(
+        conn: &BpRecordConn,
+        member_id: Uuid,
+    ) -> Result<Vec<MemberInvitations>, ApiError> {
+        let invitation_list = conn
+            .run(move |c| {
+                member_invitations::table
+                    .filter(member_invitations::member_id.eq(member_id))
+                    .filter(member_invitations::accepted_at.is_null())
+                    .filter(member_invitations::revoked_at.is_null())
+                    .filter(member_invitations::expires_at.gt(diesel::dsl::now))
+                    .order(member_invitations::created_at.desc())
+                    .get_results::<MemberInvitations>(c)
+            })
+            .await?;
+        Ok(invitation_list)
+    }
+
+    pub async fn revoke(conn: &BpRecordConn, id: Uuid) -> Result<MemberInvitations, ApiError> {
+        let invitation = conn
+            .run(move |c| {
+                diesel::update(
+                    member_invitations::table
+                        .find(id)
+                        .filter(member_invitations::accepted_at.is_null())
+                        .filter(member_invitations::revoked_at.is_null()),
+                )
+                .set((
+                    member_invitations::revoked_at.eq(diesel::dsl::now),
+                    member_invitations::updated_at.eq(diesel::dsl::now),
+                ))
+                .get_result::<MemberInvitations>(c)
+                .optional()
+            })
+            .await?;
+        invitation.ok_or_else(|| ApiError::BadRequest(String::from("邀请已失效")))
+    }
+
+    pub async fn accept(
+        conn: &BpRecordConn,
+        user_id: Uuid,
+        code: String,
+    ) -> Result<UserMember, ApiError> {
+        let code_hash = hash_secret(&code.trim().to_uppercase());
+        let user_member = conn
+            .run(move |c| {
+                c.transaction(|x| {
+                    let invitation = member_invitations::table
+                        .filter(member_invitations::code_hash.eq(code_hash))
+                        .for_update()
+                        .get_result::<MemberInvitations>(x)
+                        .optional()?;
+                    let Some(invitation) = invitation else {
+                        return Err(ApiError::BadRequest(String::from("邀请码无效")));
+                    };
+                    if invitation.accepted_at.is_some()
+                        || invitation.revoked_at.is_some()
+                        || invitation.expires_at <= Utc::now().naive_utc()
+                    {
+                        return Err(ApiError::BadRequest(String::from("邀请已失效")));
+                    }
+                    let linked = user_member::table
+                        .find((user_id, invitation.member_id))
+                        .get_result::<UserMember>(x)
+                        .optional()?;
+                    let user_member = if invitation.role == MemberRole::Owner.as_str() {
+                        MemberInvitations::transfer(x, &invitation, user_id, linked)?
+                    } else {
+                        if linked.is_some() {
+                            return Err(ApiError::BadRequest(String::from("已关联该成员")));
+                        }
+                        diesel::insert_into(user_member::table)
+                            .values((
+                                user_member::user_id.eq(user_id),
+                                user_member::member_id.eq(invitation.member_id),
+                                user_member::role.eq(&invitation.role),
+                            ))
+                            .get_result::<UserMember>(x)?
+                    };
+                    diesel::update(member_invitations::table.find(invitation.id))
+                        .set((
+                            member_invitations::accepted_by.eq(user_id),
+                            member_invitations::accepted_at.eq(diesel::dsl::now),
+                            member_invitations::updated_at.eq(diesel::dsl::now),
+                        ))
+                        .execute(x)?;
+                    Ok::<UserMember, ApiError>(user_member)
+                })
+            })
+            .await?;
+        Ok(user_member)
+    }
+
+    fn transfer(
+        x: &mut PgConnection,
+        invitation: &MemberInvitations,
+        user_id: Uuid,
+        linked: Option<UserMember>,
+    ) -> Result<UserMember, ApiError> {
+        let inviter = user_member::table
+            .find((invitation.inviter_id, invitation.member_id))
+            .get_result::<UserMember>(x)
+            .optional()?;
+        if inviter.is_none_or(|inviter| inviter.role != MemberRole::Owner.as_str()) {
+            return Err(ApiError::BadRequest(String::from("邀请已失效")));
+        }
+        if linked
+            .as_ref()
+            .is_some_and(|linked| linked.role == MemberRole::Owner.as_str())
+        {
+            return Err(ApiError::BadRequest(String::from("已是该成员的所有者")));
+        }
+        if MemberQuota::load(x, user_id)?.available <= 0 {
+            return Err(ApiError::BadRequest(String::from("成员数量已达上限")));
+        }
+        diesel::update(
+            user_member::table
+                .filter(user_member::member_id.eq(invitation.member_id))
+                .filter(user_member::role.eq(MemberRole::Owner.as_str())),
+        )
+        .set((
+            user_member::role.eq(MemberRole::Editor.as_str()),
+            user_member::updated_at.eq(diesel::dsl::now),
+        ))
+        .execute(x)?;
+        diesel::update(
+            member_invitations::table
+                .filter(member_invitations::member_id.eq(invitation.member_id))
+                .filter(member_invitations::role.eq(MemberRole::Owner.as_str()))
+                .filter(member_invitations::id.ne(invitation.id))
+                .filter(member_invitations::accepted_at.is_null())
+                .filter(member_invitations::revoked_at.is_null()),
+        )
+        .set((
+            member_invitations::revoked_at.eq(diesel::dsl::now),
+            member_invitations::updated_at.eq(diesel::dsl::now),
+        ))
+        .execute(x)?;
+        let user_member = match linked {
+            Some(_) => diesel::update(user_member::table.find((user_id, invitation.member_id)))
+                .set((
+                    user_member::role.eq(MemberRole::Owner.as_str()),
+                    user_member::updated_at.eq(diesel::dsl::now),
+                ))
+                .get_result::<UserMember>(x)?,
+            None => diesel::insert_into(user_member::table)
+                .values((
+                    user_member::user_id.eq(user_id),
+                    user_member::member_id.eq(invitation.member_id),
+                    user_member::role.eq(MemberRole::Owner.as_str()),
+                ))
+                .get_result::<UserMember>(x)?,
+        };
+        Ok(user_member)
+    }
+}
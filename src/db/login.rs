@@ -0,0 +1,62 @@
+use crate::db::BpRecordConn;
+use crate::error::api::ApiError;
+use crate::schema::login_events;
+use crate::util::client::ClientInfo;
+use crate::util::serde_time_format;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::{PgConnection, Queryable, Selectable};
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Identifiable, Queryable, Selectable)]
+#[diesel(
+    table_name = crate::schema::login_events,
+    primary_key(id),
+    check_for_backend(diesel::pg::Pg),
+)]
+pub struct LoginEvents {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub provider: String,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    #[serde(with = "serde_time_format")]
+    pub created_at: NaiveDateTime,
+}
+
+impl LoginEvents {
+    pub async fn insert(
+        conn: &BpRecordConn,
+        user_id: Uuid,
+        provider: &'static str,
+        client: ClientInfo,
+    ) -> Result<(), ApiError> {
+        conn.run(move |c| {
+            diesel::insert_into(login_events::table)
+                .values((
+                    login_events::user_id.eq(user_id),
+                    login_events::provider.eq(provider),
+                    login_events::ip.eq(client.ip),
+                    login_events::user_agent.eq(client.user_agent),
+                ))
+                .execute(c)
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub fn history(
+        c: &mut PgConnection,
+        user_id: Uuid,
+        offset: i64,
+        limit: i64,
+    ) -> QueryResult<Vec<LoginEvents>> {
+        login_events::table
+            .filter(login_events::user_id.eq(user_id))
+            .order(login_events::created_at.desc())
+            .offset(offset)
+            .limit(limit)
+            .get_results::<LoginEvents>(c)
+    }
+}
@@ -1,27 +1,19 @@
 use crate::db::BpRecordConn;
-use crate::db::record::Records;
+use crate::db::quota::MemberQuota;
+use crate::db::search::search_vector;
 use crate::db::user::Users;
 use crate::error::api::ApiError;
-use crate::schema::{members, records, user_member};
+use crate::schema::{member_invitations, members, records, records_archive, user_member};
 use crate::util::serde_time_format;
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime, Utc};
 use diesel::prelude::*;
-use diesel::{Queryable, Selectable};
-use lazy_static::lazy_static;
-use serde::{Deserialize, Serialize};
-use std::env;
+use diesel::{PgConnection, Queryable, Selectable};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashSet;
 use std::result::Result::Ok;
+use std::str::FromStr;
 use uuid::Uuid;
 
-lazy_static! {
-    pub static ref MEMBER_NUM: i64 = {
-        env::var("MEMBER_NUM")
-            .unwrap_or_else(|_| "2".to_owned())
-            .parse::<i64>()
-            .unwrap()
-    };
-}
-
 #[derive(Debug, Serialize, Identifiable, Queryable, Selectable, Associations)]
 #[diesel(
     table_name = user_member,
@@ -37,6 +29,53 @@ pub struct UserMember {
     pub created_at: NaiveDateTime,
     #[serde(with = "serde_time_format")]
     pub updated_at: NaiveDateTime,
+    pub role: String,
+    pub sort_order: Option<i32>,
+    pub pinned: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemberListItem {
+    #[serde(flatten)]
+    pub member: Members,
+    pub role: String,
+    pub pinned: bool,
+}
+
+#[derive(Deserialize)]
+pub struct MemberOrder {
+    pub member_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemberRole {
+    Viewer,
+    Editor,
+    Owner,
+}
+
+impl MemberRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemberRole::Viewer => "viewer",
+            MemberRole::Editor => "editor",
+            MemberRole::Owner => "owner",
+        }
+    }
+}
+
+impl FromStr for MemberRole {
+    type Err = ApiError;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role {
+            "viewer" => Ok(MemberRole::Viewer),
+            "editor" => Ok(MemberRole::Editor),
+            "owner" => Ok(MemberRole::Owner),
+            _ => Err(ApiError::BadRequest(format!("未知的成员角色 {}", role))),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Identifiable, Queryable, Selectable)]
@@ -53,20 +92,140 @@ pub struct Members {
     pub created_at: NaiveDateTime,
     #[serde(with = "serde_time_format")]
     pub updated_at: NaiveDateTime,
+    pub birth_date: Option<NaiveDate>,
+    pub sex: Option<String>,
+    pub height: Option<i32>,
+    pub weight: Option<f32>,
+    pub diabetes: bool,
+    pub ckd: bool,
+    pub smoking: Option<String>,
+    #[serde(skip_serializing)]
+    pub avatar_key: Option<String>,
+    pub avatar_url: Option<String>,
 }
 
-#[derive(Debug, FromForm)]
-pub struct MemberQuery {
-    #[field(name = "name")]
-    pub name: Option<String>,
-    #[field(name = "memo")]
-    pub memo: Option<String>,
+#[derive(Debug, Serialize)]
+pub struct MergeReport {
+    pub member: Members,
+    pub moved_records: usize,
+    pub removed_duplicates: usize,
+    pub removed_shares: usize,
 }
 
 #[derive(Deserialize)]
 pub struct NewMember {
     pub name: String,
     pub memo: Option<String>,
+    #[serde(default)]
+    pub birth_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub sex: Option<String>,
+    #[serde(default)]
+    pub height: Option<i32>,
+    #[serde(default)]
+    pub weight: Option<f32>,
+    #[serde(default)]
+    pub diabetes: bool,
+    #[serde(default)]
+    pub ckd: bool,
+    #[serde(default)]
+    pub smoking: Option<String>,
+}
+
+impl NewMember {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        validate_name(&self.name)?;
+        validate_profile(
+            self.birth_date,
+            self.sex.as_deref(),
+            self.height,
+            self.weight,
+            self.smoking.as_deref(),
+        )
+    }
+}
+
+/// Partial update of a member: omitted fields are left unchanged, an explicit
+/// `null` clears a nullable field.
+#[derive(Deserialize, AsChangeset)]
+#[diesel(table_name = members)]
+pub struct MemberChanges {
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "present")]
+    pub memo: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    pub birth_date: Option<Option<NaiveDate>>,
+    #[serde(default, deserialize_with = "present")]
+    pub sex: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    pub height: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "present")]
+    pub weight: Option<Option<f32>>,
+    pub diabetes: Option<bool>,
+    pub ckd: Option<bool>,
+    #[serde(default, deserialize_with = "present")]
+    pub smoking: Option<Option<String>>,
+}
+
+impl MemberChanges {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if let Some(name) = &self.name {
+            validate_name(name)?;
+        }
+        validate_profile(
+            self.birth_date.flatten(),
+            self.sex.as_ref().and_then(|sex| sex.as_deref()),
+            self.height.flatten(),
+            self.weight.flatten(),
+            self.smoking.as_ref().and_then(|smoking| smoking.as_deref()),
+        )
+    }
+}
+
+/// Keeps an explicit `null` apart from an omitted field, which `default`
+/// turns into `None`.
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+fn validate_name(name: &str) -> Result<(), ApiError> {
+    if name.trim().is_empty() {
+        return Err(ApiError::BadRequest(String::from("成员名称不能为空")));
+    }
+    Ok(())
+}
+
+fn validate_profile(
+    birth_date: Option<NaiveDate>,
+    sex: Option<&str>,
+    height: Option<i32>,
+    weight: Option<f32>,
+    smoking: Option<&str>,
+) -> Result<(), ApiError> {
+    if birth_date.is_some_and(|birth_date| birth_date > Utc::now().date_naive()) {
+        return Err(ApiError::BadRequest(String::from("出生日期不能晚于今天")));
+    }
+    if sex.is_some_and(|sex| !matches!(sex, "male" | "female")) {
+        return Err(ApiError::BadRequest(String::from(
+            "性别只能是 male 或 female",
+        )));
+    }
+    if height.is_some_and(|height| !(30..=250).contains(&height)) {
+        return Err(ApiError::BadRequest(String::from("身高超出范围")));
+    }
+    if weight.is_some_and(|weight| !(2.0..=300.0).contains(&weight)) {
+        return Err(ApiError::BadRequest(String::from("体重超出范围")));
+    }
+    if smoking.is_some_and(|smoking| !matches!(smoking, "never" | "former" | "current")) {
+        return Err(ApiError::BadRequest(String::from(
+            "吸烟状况只能是 never、former 或 current",
+        )));
+    }
+    Ok(())
 }
 
 impl Members {
@@ -74,6 +233,7 @@ impl Members {
         conn: &BpRecordConn,
         user_id: Uuid,
         member_id: Uuid,
+        required: MemberRole,
     ) -> Result<UserMember, ApiError> {
         let user_member = conn
             .run(move |c| {
@@ -83,53 +243,54 @@ impl Members {
             })
             .await
             .map_err(|_| ApiError::BadRequest(String::from("用户与成员信息不匹配")))?;
+        if user_member.role()? < required {
+            return Err(ApiError::Forbidden(String::from("没有权限执行该操作")));
+        }
         Ok(user_member)
     }
 
     pub async fn detail(conn: &BpRecordConn, id: Uuid) -> Result<Members, ApiError> {
         let member = conn
-            .run(move |c| members::table.find(id).get_result::<Members>(c))
+            .run(move |c| {
+                members::table
+                    .find(id)
+                    .select(Members::as_select())
+                    .get_result::<Members>(c)
+            })
             .await?;
         Ok(member)
     }
 
-    pub async fn select(
-        conn: &BpRecordConn,
-        query: Option<MemberQuery>,
-    ) -> Result<Vec<Members>, ApiError> {
-        let mut member_query = members::table.into_boxed();
-        if let Some(query) = query {
-            if let Some(name) = query.name {
-                member_query = member_query.filter(members::name.eq(name));
-            }
-            if let Some(memo) = query.memo {
-                member_query = member_query.filter(members::memo.like(format!("%{}%", memo)));
-            }
-        }
-        let member_list = conn.run(|c| member_query.get_results::<Members>(c)).await?;
-        Ok(member_list)
-    }
-
     pub async fn insert(
         conn: &BpRecordConn,
         user_id: Uuid,
         new_member: NewMember,
     ) -> Result<Members, ApiError> {
+        new_member.validate()?;
         let member = conn
             .run(move |c| {
                 c.transaction(|x| {
-                    let member_num: i64 = user_member::table
-                        .filter(user_member::user_id.eq(user_id))
-                        .count()
-                        .get_result(x)?;
-                    if member_num >= *MEMBER_NUM {
-                        return Err(ApiError::BadRequest(String::from("最多添加2名成员")));
+                    let quota = MemberQuota::load(x, user_id)?;
+                    if quota.available <= 0 {
+                        return Err(ApiError::BadRequest(format!(
+                            "最多添加{}名成员",
+                            quota.limit
+                        )));
                     }
                     let member = diesel::insert_into(members::table)
                         .values((
                             members::name.eq(new_member.name),
+                            members::search_vector.eq(search_vector(new_member.memo.clone())),
                             members::memo.eq(new_member.memo),
+                            members::birth_date.eq(new_member.birth_date),
+                            members::sex.eq(new_member.sex),
+                            members::height.eq(new_member.height),
+                            members::weight.eq(new_member.weight),
+                            members::diabetes.eq(new_member.diabetes),
+                            members::ckd.eq(new_member.ckd),
+                            members::smoking.eq(new_member.smoking),
                         ))
+                        .returning(Members::as_returning())
                         .get_result::<Members>(x)?;
                     diesel::insert_into(user_member::table)
                         .values((
@@ -147,69 +308,323 @@ impl Members {
     pub async fn update(
         conn: &BpRecordConn,
         member_id: Uuid,
-        new_member: NewMember,
+        changes: MemberChanges,
     ) -> Result<Members, ApiError> {
+        changes.validate()?;
         let member = conn
             .run(move |c| {
-                diesel::update(members::table.find(member_id))
-                    .set((
-                        members::name.eq(new_member.name),
-                        members::memo.eq(new_member.memo),
-                        members::updated_at.eq(diesel::dsl::now),
-                    ))
-                    .get_result::<Members>(c)
+                c.transaction(|x| {
+                    if let Some(memo) = &changes.memo {
+                        diesel::update(members::table.find(member_id))
+                            .set(members::search_vector.eq(search_vector(memo.clone())))
+                            .execute(x)?;
+                    }
+                    diesel::update(members::table.find(member_id))
+                        .set((&changes, members::updated_at.eq(diesel::dsl::now)))
+                        .returning(Members::as_returning())
+                        .get_result::<Members>(x)
+                })
             })
             .await?;
         Ok(member)
     }
 
-    pub async fn delete(
-        conn: &BpRecordConn,
-        user_id: Uuid,
-        member_id: Uuid,
-    ) -> Result<usize, ApiError> {
+    pub async fn delete(conn: &BpRecordConn, user_member: UserMember) -> Result<usize, ApiError> {
+        let owner = user_member.role()? == MemberRole::Owner;
         let result = conn
             .run(move |c| {
                 c.transaction(|x| {
-                    let record_list = records::table
-                        .filter(records::member_id.eq(member_id))
-                        .get_results::<Records>(x);
-                    if record_list.is_ok() {
-                        diesel::delete(records::table.filter(records::member_id.eq(member_id)))
-                            .execute(x)?;
-                    }
-                    let user_member = user_member::table
-                        .find((user_id, member_id))
-                        .get_result::<UserMember>(x);
-                    if let Ok(user_member) = user_member {
-                        diesel::delete(
+                    if !owner {
+                        return diesel::delete(
                             user_member::table.find((user_member.user_id, user_member.member_id)),
                         )
-                        .execute(x)?;
+                        .execute(x);
                     }
+                    let member_id = user_member.member_id;
+                    diesel::delete(records::table.filter(records::member_id.eq(member_id)))
+                        .execute(x)?;
+                    diesel::delete(
+                        records_archive::table.filter(records_archive::member_id.eq(member_id)),
+                    )
+                    .execute(x)?;
+                    diesel::delete(
+                        member_invitations::table
+                            .filter(member_invitations::member_id.eq(member_id)),
+                    )
+                    .execute(x)?;
+                    diesel::delete(user_member::table.filter(user_member::member_id.eq(member_id)))
+                        .execute(x)?;
                     diesel::delete(members::table.find(member_id)).execute(x)
                 })
             })
             .await?;
         Ok(result)
     }
+
+    pub async fn update_avatar(
+        conn: &BpRecordConn,
+        member_id: Uuid,
+        avatar_key: Option<String>,
+        avatar_url: Option<String>,
+    ) -> Result<Members, ApiError> {
+        let member = conn
+            .run(move |c| {
+                diesel::update(members::table.find(member_id))
+                    .set((
+                        members::avatar_key.eq(avatar_key),
+                        members::avatar_url.eq(avatar_url),
+                        members::updated_at.eq(diesel::dsl::now),
+                    ))
+                    .returning(Members::as_returning())
+                    .get_result::<Members>(c)
+            })
+            .await?;
+        Ok(member)
+    }
+
+    /// Moves the source member's records into the target and deletes the
+    /// source, inside the caller's transaction. Both members must be owned by
+    /// `user_id`. Records already present on the target, live or archived,
+    /// are dropped as duplicates. The source's shares are removed, not carried over, so
+    /// merging never widens who can see the records.
+    pub fn merge(
+        x: &mut PgConnection,
+        user_id: Uuid,
+        target_id: Uuid,
+        source_id: Uuid,
+    ) -> Result<MergeReport, ApiError> {
+        if target_id == source_id {
+            return Err(ApiError::BadRequest(String::from("不能合并同一个成员")));
+        }
+        for member_id in [target_id, source_id] {
+            let owned = diesel::select(diesel::dsl::exists(
+                user_member::table
+                    .find((user_id, member_id))
+                    .filter(user_member::role.eq(MemberRole::Owner.as_str())),
+            ))
+            .get_result::<bool>(x)?;
+            if !owned {
+                return Err(ApiError::BadRequest(String::from("用户与成员信息不匹配")));
+            }
+        }
+        let target_records = diesel::alias!(records as target_records);
+        let mut removed_duplicates = diesel::delete(
+            records::table
+                .filter(records::member_id.eq(source_id))
+                .filter(diesel::dsl::exists(
+                    target_records
+                        .filter(target_records.field(records::member_id).eq(target_id))
+                        .filter(
+                            target_records
+                                .field(records::record_at)
+                                .eq(records::record_at),
+                        )
+                        .filter(
+                            target_records
+                                .field(records::systolic)
+                                .eq(records::systolic),
+                        )
+                        .filter(
+                            target_records
+                                .field(records::diastolic)
+                                .eq(records::diastolic),
+                        )
+                        .filter(target_records.field(records::bmp).eq(records::bmp)),
+                )),
+        )
+        .execute(x)?;
+        let target_archive = diesel::alias!(records_archive as target_archive);
+        removed_duplicates += diesel::delete(
+            records_archive::table
+                .filter(records_archive::member_id.eq(source_id))
+                .filter(diesel::dsl::exists(
+                    target_archive
+                        .filter(
+                            target_archive
+                                .field(records_archive::member_id)
+                                .eq(target_id),
+                        )
+                        .filter(
+                            target_archive
+                                .field(records_archive::record_at)
+                                .eq(records_archive::record_at),
+                        )
+                        .filter(
+                            target_archive
+                                .field(records_archive::systolic)
+                                .eq(records_archive::systolic),
+                        )
+                        .filter(
+                            target_archive
+                                .field(records_archive::diastolic)
+                                .eq(records_archive::diastolic),
+                        )
+                        .filter(
+                            target_archive
+                                .field(records_archive::bmp)
+                                .eq(records_archive::bmp),
+                        ),
+                )),
+        )
+        .execute(x)?;
+        // A reading may be live on one member and already archived on the
+        // other, so each table is also checked against the other one.
+        removed_duplicates += diesel::delete(
+            records::table
+                .filter(records::member_id.eq(source_id))
+                .filter(diesel::dsl::exists(
+                    records_archive::table
+                        .filter(records_archive::member_id.eq(target_id))
+                        .filter(records_archive::record_at.eq(records::record_at))
+                        .filter(records_archive::systolic.eq(records::systolic))
+                        .filter(records_archive::diastolic.eq(records::diastolic))
+                        .filter(records_archive::bmp.eq(records::bmp)),
+                )),
+        )
+        .execute(x)?;
+        removed_duplicates += diesel::delete(
+            records_archive::table
+                .filter(records_archive::member_id.eq(source_id))
+                .filter(diesel::dsl::exists(
+                    records::table
+                        .filter(records::member_id.eq(target_id))
+                        .filter(records::record_at.eq(records_archive::record_at))
+                        .filter(records::systolic.eq(records_archive::systolic))
+                        .filter(records::diastolic.eq(records_archive::diastolic))
+                        .filter(records::bmp.eq(records_archive::bmp)),
+                )),
+        )
+        .execute(x)?;
+        let mut moved_records =
+            diesel::update(records::table.filter(records::member_id.eq(source_id)))
+                .set(records::member_id.eq(target_id))
+                .execute(x)?;
+        moved_records +=
+            diesel::update(records_archive::table.filter(records_archive::member_id.eq(source_id)))
+                .set(records_archive::member_id.eq(target_id))
+                .execute(x)?;
+
+        let removed_shares = diesel::delete(
+            user_member::table
+                .filter(user_member::member_id.eq(source_id))
+                .filter(user_member::user_id.ne(user_id)),
+        )
+        .execute(x)?;
+        diesel::delete(user_member::table.find((user_id, source_id))).execute(x)?;
+        diesel::delete(
+            member_invitations::table.filter(member_invitations::member_id.eq(source_id)),
+        )
+        .execute(x)?;
+        diesel::delete(members::table.find(source_id)).execute(x)?;
+        let member = diesel::update(members::table.find(target_id))
+            .set(members::updated_at.eq(diesel::dsl::now))
+            .returning(Members::as_returning())
+            .get_result::<Members>(x)?;
+        Ok(MergeReport {
+            member,
+            moved_records,
+            removed_duplicates,
+            removed_shares,
+        })
+    }
+
+    pub fn age(&self) -> Option<u32> {
+        self.birth_date
+            .and_then(|birth_date| Utc::now().date_naive().years_since(birth_date))
+    }
+
+    pub fn bmi(&self) -> Option<f32> {
+        let height = self.height? as f32 / 100.0;
+        self.weight.map(|weight| weight / (height * height))
+    }
 }
 
 impl UserMember {
+    pub fn role(&self) -> Result<MemberRole, ApiError> {
+        MemberRole::from_str(&self.role)
+    }
+
     pub async fn get_user_members(
         conn: &BpRecordConn,
         user_id: Uuid,
-    ) -> Result<Vec<Members>, ApiError> {
+    ) -> Result<Vec<MemberListItem>, ApiError> {
         let member_list = conn
             .run(move |c| {
                 members::table
                     .inner_join(user_member::table)
                     .filter(user_member::user_id.eq(user_id))
-                    .order(members::updated_at.desc())
-                    .select(Members::as_select())
-                    .get_results::<Members>(c)
+                    .order((
+                        user_member::pinned.desc(),
+                        user_member::sort_order.asc().nulls_last(),
+                        members::updated_at.desc(),
+                    ))
+                    .select((Members::as_select(), user_member::role, user_member::pinned))
+                    .get_results::<(Members, String, bool)>(c)
+            })
+            .await?;
+        Ok(member_list
+            .into_iter()
+            .map(|(member, role, pinned)| MemberListItem {
+                member,
+                role,
+                pinned,
+            })
+            .collect())
+    }
+
+    pub async fn reorder(
+        conn: &BpRecordConn,
+        user_id: Uuid,
+        member_order: MemberOrder,
+    ) -> Result<(), ApiError> {
+        let member_ids = member_order.member_ids;
+        if member_ids.iter().collect::<HashSet<_>>().len() != member_ids.len() {
+            return Err(ApiError::BadRequest(String::from("成员排序不能重复")));
+        }
+        conn.run(move |c| {
+            c.transaction(|x| {
+                let linked = user_member::table
+                    .filter(user_member::user_id.eq(user_id))
+                    .filter(user_member::member_id.eq_any(&member_ids))
+                    .count()
+                    .get_result::<i64>(x)?;
+                if linked != member_ids.len() as i64 {
+                    return Err(ApiError::BadRequest(String::from("用户与成员信息不匹配")));
+                }
+                diesel::update(user_member::table.filter(user_member::user_id.eq(user_id)))
+                    .set(user_member::sort_order.eq(None::<i32>))
+                    .execute(x)?;
+                for (index, member_id) in member_ids.into_iter().enumerate() {
+                    diesel::update(user_member::table.find((user_id, member_id)))
+                        .set((
+                            user_member::sort_order.eq(index as i32),
+                            user_member::updated_at.eq(diesel::dsl::now),
+                        ))
+                        .execute(x)?;
+                }
+                Ok::<(), ApiError>(())
+            })
+        })
+        .await
+    }
+
+    pub async fn pin(
+        conn: &BpRecordConn,
+        user_member: UserMember,
+        pinned: bool,
+    ) -> Result<UserMember, ApiError> {
+        let user_member = conn
+            .run(move |c| {
+                diesel::update(
+                    user_member::table.find((user_member.user_id, user_member.member_id)),
+                )
+                .set((
+                    user_member::pinned.eq(pinned),
+                    user_member::updated_at.eq(diesel::dsl::now),
+                ))
+                .get_result::<UserMember>(c)
             })
             .await?;
-        Ok(member_list)
+        Ok(user_member)
     }
 }
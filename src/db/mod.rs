@@ -1,8 +1,28 @@
-use rocket_sync_db_pools::database;
+use rocket_sync_db_pools::{ConnectionPool, database};
 
+pub mod admin;
+pub mod erasure;
+pub mod export;
+pub mod identity;
+pub mod invitation;
+pub mod login;
 pub mod member;
+pub mod profile;
+pub mod quota;
 pub mod record;
+pub mod revocation;
+pub mod search;
+pub mod sms;
+pub mod token;
 pub mod user;
 
 #[database("bp-record")]
 pub struct BpRecordConn(diesel::PgConnection);
+
+pub type BpRecordPool = ConnectionPool<BpRecordConn, diesel::PgConnection>;
+
+impl BpRecordConn {
+    pub async fn from_pool(pool: &BpRecordPool) -> Option<Self> {
+        pool.get().await.map(BpRecordConn)
+    }
+}
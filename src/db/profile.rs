@@ -0,0 +1,184 @@
+use crate::db::BpRecordConn;
+use crate::error::api::ApiError;
+use crate::schema::user_profiles;
+use crate::util::cache::TtlCache;
+use crate::util::serde_time_format;
+use crate::util::timezone::DEFAULT_TIMEZONE;
+use chrono::{NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use diesel::prelude::*;
+use diesel::{Queryable, Selectable};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::env;
+use uuid::Uuid;
+
+lazy_static! {
+    pub static ref PROFILE_CACHE_TTL: u64 = {
+        env::var("PROFILE_CACHE_TTL")
+            .unwrap_or_else(|_| "300".to_owned())
+            .parse::<u64>()
+            .unwrap()
+    };
+    static ref TIMEZONES: TtlCache<Uuid, Tz> =
+        TtlCache::new(PROFILE_CACHE_SIZE, *PROFILE_CACHE_TTL);
+}
+
+const PROFILE_CACHE_SIZE: usize = 10000;
+
+const DEFAULT_LANGUAGE: &str = "zh-CN";
+const DEFAULT_BP_UNIT: &str = "mmHg";
+const DEFAULT_DATE_FORMAT: &str = "YYYY-MM-DD";
+
+#[derive(Debug, Serialize, Identifiable, Queryable, Selectable)]
+#[diesel(
+    table_name = crate::schema::user_profiles,
+    primary_key(user_id),
+    check_for_backend(diesel::pg::Pg),
+)]
+pub struct UserProfiles {
+    pub user_id: Uuid,
+    pub timezone: String,
+    pub language: String,
+    pub bp_unit: String,
+    pub date_format: String,
+    #[serde(with = "serde_time_format")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "serde_time_format")]
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Deserialize)]
+pub struct NewProfile {
+    pub timezone: Option<String>,
+    pub language: Option<String>,
+    pub bp_unit: Option<String>,
+    pub date_format: Option<String>,
+}
+
+impl NewProfile {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if self
+            .timezone
+            .as_deref()
+            .is_some_and(|timezone| timezone.parse::<Tz>().is_err())
+        {
+            return Err(ApiError::BadRequest(String::from("未知的时区")));
+        }
+        if self
+            .language
+            .as_deref()
+            .is_some_and(|language| !matches!(language, "zh-CN" | "zh-TW" | "en-US"))
+        {
+            return Err(ApiError::BadRequest(String::from(
+                "语言只能是 zh-CN、zh-TW 或 en-US",
+            )));
+        }
+        if self
+            .bp_unit
+            .as_deref()
+            .is_some_and(|bp_unit| !matches!(bp_unit, "mmHg" | "kPa"))
+        {
+            return Err(ApiError::BadRequest(String::from(
+                "血压单位只能是 mmHg 或 kPa",
+            )));
+        }
+        if self.date_format.as_deref().is_some_and(|date_format| {
+            !matches!(date_format, "YYYY-MM-DD" | "DD/MM/YYYY" | "MM/DD/YYYY")
+        }) {
+            return Err(ApiError::BadRequest(String::from(
+                "日期格式只能是 YYYY-MM-DD、DD/MM/YYYY 或 MM/DD/YYYY",
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl UserProfiles {
+    /// Returns the stored profile, or the defaults if the user never set one.
+    pub async fn detail(conn: &BpRecordConn, user_id: Uuid) -> Result<UserProfiles, ApiError> {
+        let profile = conn
+            .run(move |c| {
+                user_profiles::table
+                    .find(user_id)
+                    .get_result::<UserProfiles>(c)
+                    .optional()
+            })
+            .await?;
+        Ok(profile.unwrap_or_else(|| {
+            let now = Utc::now().naive_utc();
+            UserProfiles {
+                user_id,
+                timezone: DEFAULT_TIMEZONE.name().to_owned(),
+                language: DEFAULT_LANGUAGE.to_owned(),
+                bp_unit: DEFAULT_BP_UNIT.to_owned(),
+                date_format: DEFAULT_DATE_FORMAT.to_owned(),
+                created_at: now,
+                updated_at: now,
+            }
+        }))
+    }
+
+    pub async fn update(
+        conn: &BpRecordConn,
+        user_id: Uuid,
+        new_profile: NewProfile,
+    ) -> Result<UserProfiles, ApiError> {
+        new_profile.validate()?;
+        let current = UserProfiles::detail(conn, user_id).await?;
+        let timezone = new_profile.timezone.unwrap_or(current.timezone);
+        let language = new_profile.language.unwrap_or(current.language);
+        let bp_unit = new_profile.bp_unit.unwrap_or(current.bp_unit);
+        let date_format = new_profile.date_format.unwrap_or(current.date_format);
+        let profile = conn
+            .run(move |c| {
+                diesel::insert_into(user_profiles::table)
+                    .values((
+                        user_profiles::user_id.eq(user_id),
+                        user_profiles::timezone.eq(&timezone),
+                        user_profiles::language.eq(&language),
+                        user_profiles::bp_unit.eq(&bp_unit),
+                        user_profiles::date_format.eq(&date_format),
+                    ))
+                    .on_conflict(user_profiles::user_id)
+                    .do_update()
+                    .set((
+                        user_profiles::timezone.eq(&timezone),
+                        user_profiles::language.eq(&language),
+                        user_profiles::bp_unit.eq(&bp_unit),
+                        user_profiles::date_format.eq(&date_format),
+                        user_profiles::updated_at.eq(diesel::dsl::now),
+                    ))
+                    .get_result::<UserProfiles>(c)
+            })
+            .await?;
+        TIMEZONES.put(
+            user_id,
+            profile.timezone.parse().unwrap_or(*DEFAULT_TIMEZONE),
+        );
+        Ok(profile)
+    }
+
+    pub fn cached_timezone(user_id: Uuid) -> Option<Tz> {
+        TIMEZONES.get(&user_id)
+    }
+
+    pub async fn timezone(conn: &BpRecordConn, user_id: Uuid) -> Result<Tz, ApiError> {
+        if let Some(timezone) = TIMEZONES.get(&user_id) {
+            return Ok(timezone);
+        }
+        let timezone = conn
+            .run(move |c| {
+                user_profiles::table
+                    .find(user_id)
+                    .select(user_profiles::timezone)
+                    .get_result::<String>(c)
+                    .optional()
+            })
+            .await?
+            .and_then(|timezone| timezone.parse::<Tz>().ok())
+            .unwrap_or(*DEFAULT_TIMEZONE);
+        TIMEZONES.put(user_id, timezone);
+        Ok(timezone)
+    }
+}
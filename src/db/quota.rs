@@ -0,0 +1,94 @@
+use crate::db::BpRecordConn;
+use crate::db::member::MemberRole;
+use crate::error::api::ApiError;
+use crate::schema::{quota_plans, user_member, user_quotas};
+use diesel::PgConnection;
+use diesel::prelude::*;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::env;
+use uuid::Uuid;
+
+lazy_static! {
+    pub static ref MEMBER_NUM: i64 = {
+        env::var("MEMBER_NUM")
+            .unwrap_or_else(|_| "2".to_owned())
+            .parse::<i64>()
+            .unwrap()
+    };
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemberQuota {
+    pub limit: i64,
+    pub used: i64,
+    pub available: i64,
+}
+
+impl MemberQuota {
+    pub async fn detail(conn: &BpRecordConn, user_id: Uuid) -> Result<MemberQuota, ApiError> {
+        let quota = conn.run(move |c| MemberQuota::load(c, user_id)).await?;
+        Ok(quota)
+    }
+
+    /// Creates the default plan from `MEMBER_NUM` unless one already exists,
+    /// so later changes to the plan are kept across restarts.
+    pub async fn seed_default_plan(conn: &BpRecordConn) -> Result<usize, ApiError> {
+        let num = conn
+            .run(move |c| {
+                diesel::insert_into(quota_plans::table)
+                    .values((
+                        quota_plans::name.eq("default"),
+                        quota_plans::member_limit.eq(*MEMBER_NUM as i32),
+                        quota_plans::is_default.eq(true),
+                    ))
+                    .on_conflict_do_nothing()
+                    .execute(c)
+            })
+            .await?;
+        Ok(num)
+    }
+
+    pub fn load(c: &mut PgConnection, user_id: Uuid) -> QueryResult<MemberQuota> {
+        let limit = MemberQuota::member_limit(c, user_id)?;
+        let used: i64 = user_member::table
+            .filter(user_member::user_id.eq(user_id))
+            .filter(user_member::role.eq(MemberRole::Owner.as_str()))
+            .count()
+            .get_result(c)?;
+        Ok(MemberQuota {
+            limit,
+            used,
+            available: (limit - used).max(0),
+        })
+    }
+
+    pub fn member_limit(c: &mut PgConnection, user_id: Uuid) -> QueryResult<i64> {
+        let user_quota = user_quotas::table
+            .find(user_id)
+            .select((user_quotas::plan_id, user_quotas::member_limit))
+            .get_result::<(Option<Uuid>, Option<i32>)>(c)
+            .optional()?;
+        if let Some((plan_id, member_limit)) = user_quota {
+            if let Some(member_limit) = member_limit {
+                return Ok(member_limit as i64);
+            }
+            if let Some(plan_id) = plan_id {
+                let plan_limit = quota_plans::table
+                    .find(plan_id)
+                    .select(quota_plans::member_limit)
+                    .get_result::<i32>(c)
+                    .optional()?;
+                if let Some(plan_limit) = plan_limit {
+                    return Ok(plan_limit as i64);
+                }
+            }
+        }
+        let default_limit = quota_plans::table
+            .filter(quota_plans::is_default.eq(true))
+            .select(quota_plans::member_limit)
+            .get_result::<i32>(c)
+            .optional()?;
+        Ok(default_limit.map_or(*MEMBER_NUM, |limit| limit as i64))
+    }
+}
@@ -1,13 +1,17 @@
 use crate::db::BpRecordConn;
 use crate::db::member::Members;
+use crate::db::search::search_vector;
 use crate::error::api::ApiError;
-use crate::schema::{members, records};
+use crate::schema::{members, records, records_archive};
 use crate::util::serde_time_format;
 use chrono::{Months, NaiveDateTime, Utc};
 use diesel::prelude::*;
+use diesel::sql_types::Timestamptz;
 use diesel::{Queryable, Selectable};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashSet;
 use std::env;
 use uuid::Uuid;
 
@@ -18,6 +22,12 @@ lazy_static! {
             .parse::<u32>()
             .unwrap()
     };
+    pub static ref RECORD_ARCHIVE_MONTH: u32 = {
+        env::var("RECORD_ARCHIVE_MONTH")
+            .unwrap_or_else(|_| "12".to_owned())
+            .parse::<u32>()
+            .unwrap()
+    };
 }
 
 #[derive(Debug, Serialize, Identifiable, Queryable, Selectable)]
@@ -38,6 +48,30 @@ pub struct Records {
     pub created_at: NaiveDateTime,
     #[serde(with = "serde_time_format")]
     pub updated_at: NaiveDateTime,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize, Identifiable, Queryable, Selectable)]
+#[diesel(
+    table_name = crate::schema::records_archive,
+    primary_key(id),
+    check_for_backend(diesel::pg::Pg),
+)]
+pub struct ArchivedRecords {
+    pub id: Uuid,
+    pub member_id: Uuid,
+    pub systolic: i32,
+    pub diastolic: i32,
+    pub bmp: i32,
+    #[serde(with = "serde_time_format")]
+    pub record_at: NaiveDateTime,
+    #[serde(with = "serde_time_format")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "serde_time_format")]
+    pub updated_at: NaiveDateTime,
+    #[serde(with = "serde_time_format")]
+    pub archived_at: NaiveDateTime,
+    pub note: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -47,9 +81,12 @@ pub struct NewRecord {
     pub bmp: i32,
     #[serde(with = "serde_time_format")]
     pub record_at: NaiveDateTime,
+    #[serde(default)]
+    pub note: Option<String>,
 }
 
 impl Records {
+    /// Lists the records of the last `RECORD_MONTH` months.
     pub async fn get_member_record(
         conn: &BpRecordConn,
         member_id: Uuid,
@@ -61,8 +98,7 @@ impl Records {
                     .unwrap()
                     .naive_utc();
                 records::table
-                    .inner_join(members::table)
-                    .filter(members::id.eq(member_id))
+                    .filter(records::member_id.eq(member_id))
                     .filter(records::record_at.ge(record_months_ago))
                     .order((records::record_at.desc(), records::updated_at.desc()))
                     .select(Records::as_select())
@@ -72,9 +108,54 @@ impl Records {
         Ok(record_list)
     }
 
-    pub async fn detail(conn: &BpRecordConn, record_id: Uuid) -> Result<Records, ApiError> {
+    /// Lists every record of the member, live and archived, for exports.
+    pub async fn get_full_history(
+        conn: &BpRecordConn,
+        member_id: Uuid,
+    ) -> Result<Vec<Records>, ApiError> {
+        let record_list = conn
+            .run(move |c| {
+                let live = records::table
+                    .filter(records::member_id.eq(member_id))
+                    .select(Records::as_select())
+                    .get_results::<Records>(c)?;
+                let archived = records_archive::table
+                    .filter(records_archive::member_id.eq(member_id))
+                    .select(ArchivedRecords::as_select())
+                    .get_results::<ArchivedRecords>(c)?;
+                Ok::<_, diesel::result::Error>(merge_history(live, archived))
+            })
+            .await?;
+        Ok(record_list)
+    }
+
+    pub async fn latest(conn: &BpRecordConn, member_id: Uuid) -> Result<Option<Records>, ApiError> {
         let record = conn
-            .run(move |c| records::table.find(record_id).get_result::<Records>(c))
+            .run(move |c| {
+                records::table
+                    .filter(records::member_id.eq(member_id))
+                    .order((records::record_at.desc(), records::updated_at.desc()))
+                    .select(Records::as_select())
+                    .first::<Records>(c)
+                    .optional()
+            })
+            .await?;
+        Ok(record)
+    }
+
+    pub async fn detail(
+        conn: &BpRecordConn,
+        member_id: Uuid,
+        record_id: Uuid,
+    ) -> Result<Records, ApiError> {
+        let record = conn
+            .run(move |c| {
+                records::table
+                    .find(record_id)
+                    .filter(records::member_id.eq(member_id))
+                    .select(Records::as_select())
+                    .get_result::<Records>(c)
+            })
             .await?;
         Ok(record)
     }
@@ -94,11 +175,15 @@ impl Records {
                             records::diastolic.eq(new_record.diastolic),
                             records::bmp.eq(new_record.bmp),
                             records::record_at.eq(new_record.record_at),
+                            records::search_vector.eq(search_vector(new_record.note.clone())),
+                            records::note.eq(new_record.note),
                         ))
+                        .returning(Records::as_returning())
                         .get_result::<Records>(x);
                     if record.is_ok() {
                         diesel::update(members::table.find(member_id))
                             .set(members::updated_at.eq(diesel::dsl::now))
+                            .returning(Members::as_returning())
                             .get_result::<Members>(x)?;
                     }
                     record
@@ -108,6 +193,99 @@ impl Records {
         Ok(record)
     }
 
+    /// Inserts the readings the member does not already have, live or
+    /// archived, and returns them with the positions of the duplicates. A
+    /// reading is a duplicate when its time and all three values match.
+    pub async fn insert_batch(
+        conn: &BpRecordConn,
+        member_id: Uuid,
+        new_records: Vec<NewRecord>,
+    ) -> Result<(Vec<Records>, Vec<usize>), ApiError> {
+        let inserted = conn
+            .run(move |c| {
+                c.transaction(|x| {
+                    // Serializes imports for the member, so two uploads of one
+                    // bundle cannot both miss each other's rows.
+                    members::table
+                        .find(member_id)
+                        .select(members::id)
+                        .for_update()
+                        .execute(x)?;
+                    let record_ats = new_records
+                        .iter()
+                        .map(|new_record| new_record.record_at)
+                        .collect::<Vec<_>>();
+                    let mut existing = records::table
+                        .filter(records::member_id.eq(member_id))
+                        .filter(records::record_at.eq_any(&record_ats))
+                        .select((
+                            records::record_at,
+                            records::systolic,
+                            records::diastolic,
+                            records::bmp,
+                        ))
+                        .load::<(NaiveDateTime, i32, i32, i32)>(x)?
+                        .into_iter()
+                        .collect::<HashSet<_>>();
+                    existing.extend(
+                        records_archive::table
+                            .filter(records_archive::member_id.eq(member_id))
+                            .filter(records_archive::record_at.eq_any(&record_ats))
+                            .select((
+                                records_archive::record_at,
+                                records_archive::systolic,
+                                records_archive::diastolic,
+                                records_archive::bmp,
+                            ))
+                            .load::<(NaiveDateTime, i32, i32, i32)>(x)?,
+                    );
+                    let mut duplicates = Vec::new();
+                    let values = new_records
+                        .into_iter()
+                        .enumerate()
+                        .filter(|(position, new_record)| {
+                            let key = (
+                                new_record.record_at,
+                                new_record.systolic,
+                                new_record.diastolic,
+                                new_record.bmp,
+                            );
+                            if existing.insert(key) {
+                                return true;
+                            }
+                            duplicates.push(*position);
+                            false
+                        })
+                        .map(|(_, new_record)| {
+                            (
+                                records::member_id.eq(member_id),
+                                records::systolic.eq(new_record.systolic),
+                                records::diastolic.eq(new_record.diastolic),
+                                records::bmp.eq(new_record.bmp),
+                                records::record_at.eq(new_record.record_at),
+                                records::search_vector.eq(search_vector(new_record.note.clone())),
+                                records::note.eq(new_record.note),
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    if values.is_empty() {
+                        return Ok((Vec::new(), duplicates));
+                    }
+                    let record_list = diesel::insert_into(records::table)
+                        .values(values)
+                        .returning(Records::as_returning())
+                        .get_results::<Records>(x)?;
+                    diesel::update(members::table.find(member_id))
+                        .set(members::updated_at.eq(diesel::dsl::now))
+                        .returning(Members::as_returning())
+                        .get_result::<Members>(x)?;
+                    Ok::<_, diesel::result::Error>((record_list, duplicates))
+                })
+            })
+            .await?;
+        Ok(inserted)
+    }
+
     pub async fn update(
         conn: &BpRecordConn,
         record_id: Uuid,
@@ -117,19 +295,26 @@ impl Records {
         let record = conn
             .run(move |c| {
                 c.transaction(|x| {
-                    let record = diesel::update(records::table.find(record_id))
-                        .set((
-                            records::member_id.eq(member_id),
-                            records::systolic.eq(new_record.systolic),
-                            records::diastolic.eq(new_record.diastolic),
-                            records::bmp.eq(new_record.bmp),
-                            records::record_at.eq(new_record.record_at),
-                            records::updated_at.eq(diesel::dsl::now),
-                        ))
-                        .get_result::<Records>(x);
+                    let record = diesel::update(
+                        records::table
+                            .find(record_id)
+                            .filter(records::member_id.eq(member_id)),
+                    )
+                    .set((
+                        records::systolic.eq(new_record.systolic),
+                        records::diastolic.eq(new_record.diastolic),
+                        records::bmp.eq(new_record.bmp),
+                        records::record_at.eq(new_record.record_at),
+                        records::search_vector.eq(search_vector(new_record.note.clone())),
+                        records::note.eq(new_record.note),
+                        records::updated_at.eq(diesel::dsl::now),
+                    ))
+                    .returning(Records::as_returning())
+                    .get_result::<Records>(x);
                     if record.is_ok() {
                         diesel::update(members::table.find(member_id))
                             .set(members::updated_at.eq(diesel::dsl::now))
+                            .returning(Members::as_returning())
                             .get_result::<Members>(x)?;
                     }
                     record
@@ -139,20 +324,106 @@ impl Records {
         Ok(record)
     }
 
-    pub async fn delete(conn: &BpRecordConn, record_id: Uuid) -> Result<usize, ApiError> {
+    pub async fn delete(
+        conn: &BpRecordConn,
+        member_id: Uuid,
+        record_id: Uuid,
+    ) -> Result<usize, ApiError> {
         let num = conn
             .run(move |c| {
                 c.transaction(|x| {
-                    let record = records::table.find(record_id).get_result::<Records>(x);
-                    if let Ok(record) = record {
-                        diesel::update(members::table.find(record.member_id))
+                    let num = diesel::delete(
+                        records::table
+                            .find(record_id)
+                            .filter(records::member_id.eq(member_id)),
+                    )
+                    .execute(x)?;
+                    if num > 0 {
+                        diesel::update(members::table.find(member_id))
                             .set(members::updated_at.eq(diesel::dsl::now))
+                            .returning(Members::as_returning())
                             .get_result::<Members>(x)?;
                     }
-                    diesel::delete(records::table.find(record_id)).execute(x)
+                    Ok::<usize, diesel::result::Error>(num)
                 })
             })
             .await?;
         Ok(num)
     }
+
+    /// Moves records older than `RECORD_ARCHIVE_MONTH` to the archive. The
+    /// delete and the insert are one statement, so a row that becomes
+    /// eligible while the job runs is either moved whole or left in place.
+    pub async fn archive(conn: &BpRecordConn) -> Result<usize, ApiError> {
+        let num = conn
+            .run(move |c| {
+                let archive_before = Utc::now()
+                    .checked_sub_months(Months::new(*RECORD_ARCHIVE_MONTH))
+                    .unwrap()
+                    .naive_utc();
+                diesel::sql_query(
+                    "WITH moved AS (
+                        DELETE FROM records WHERE record_at < $1
+                        RETURNING id, member_id, systolic, diastolic, bmp, record_at,
+                                  created_at, updated_at, note, search_vector
+                    )
+                    INSERT INTO records_archive (id, member_id, systolic, diastolic, bmp,
+                                                 record_at, created_at, updated_at, note,
+                                                 search_vector)
+                    SELECT id, member_id, systolic, diastolic, bmp, record_at,
+                           created_at, updated_at, note, search_vector
+                    FROM moved",
+                )
+                .bind::<Timestamptz, _>(archive_before)
+                .execute(c)
+            })
+            .await?;
+        Ok(num)
+    }
+}
+
+impl From<ArchivedRecords> for Records {
+    fn from(record: ArchivedRecords) -> Self {
+        Records {
+            id: record.id,
+            member_id: record.member_id,
+            systolic: record.systolic,
+            diastolic: record.diastolic,
+            bmp: record.bmp,
+            record_at: record.record_at,
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+            note: record.note,
+        }
+    }
+}
+
+/// Combines live and archived records, newest first, as the listings order
+/// them.
+pub fn merge_history(live: Vec<Records>, archived: Vec<ArchivedRecords>) -> Vec<Records> {
+    let mut record_list = live;
+    record_list.extend(archived.into_iter().map(Records::from));
+    record_list.sort_by_key(|record| Reverse((record.record_at, record.updated_at)));
+    record_list
+}
+
+impl ArchivedRecords {
+    pub async fn get_member_archive(
+        conn: &BpRecordConn,
+        member_id: Uuid,
+    ) -> Result<Vec<ArchivedRecords>, ApiError> {
+        let record_list = conn
+            .run(move |c| {
+                records_archive::table
+                    .filter(records_archive::member_id.eq(member_id))
+                    .order((
+                        records_archive::record_at.desc(),
+                        records_archive::updated_at.desc(),
+                    ))
+                    .select(ArchivedRecords::as_select())
+                    .get_results::<ArchivedRecords>(c)
+            })
+            .await?;
+        Ok(record_list)
+    }
 }
@@ -0,0 +1,181 @@
+use crate::db::BpRecordConn;
+use crate::db::admin::ADMIN;
+use crate::error::api::ApiError;
+use crate::schema::{revoked_tokens, users};
+use crate::util::cache::TtlCache;
+use crate::util::jwt::Claims;
+use chrono::{DateTime, NaiveDateTime};
+use diesel::prelude::*;
+use diesel::{Queryable, Selectable};
+use lazy_static::lazy_static;
+use std::env;
+use uuid::Uuid;
+
+lazy_static! {
+    pub static ref REVOCATION_CACHE_SIZE: usize = {
+        env::var("REVOCATION_CACHE_SIZE")
+            .unwrap_or_else(|_| "10000".to_owned())
+            .parse::<usize>()
+            .unwrap()
+    };
+    pub static ref REVOCATION_CACHE_TTL: u64 = {
+        env::var("REVOCATION_CACHE_TTL")
+            .unwrap_or_else(|_| "60".to_owned())
+            .parse::<u64>()
+            .unwrap()
+    };
+    static ref REVOKED: TtlCache<Uuid, bool> =
+        TtlCache::new(*REVOCATION_CACHE_SIZE, *REVOCATION_CACHE_TTL);
+    static ref ACCOUNTS: TtlCache<Uuid, Option<TokenAccount>> =
+        TtlCache::new(*REVOCATION_CACHE_SIZE, *REVOCATION_CACHE_TTL);
+}
+
+/// What token checks need from the user row, cached as one entry so the
+/// `Admin` guard reads the role from the same lookup as the generation.
+#[derive(Debug, Clone, Copy)]
+pub struct TokenAccount {
+    pub generation: i32,
+    pub admin: bool,
+}
+
+impl TokenAccount {
+    fn new((generation, role): (i32, String)) -> Self {
+        TokenAccount {
+            generation,
+            admin: role == ADMIN,
+        }
+    }
+}
+
+#[derive(Debug, Identifiable, Queryable, Selectable)]
+#[diesel(
+    table_name = crate::schema::revoked_tokens,
+    primary_key(jti),
+    check_for_backend(diesel::pg::Pg),
+)]
+pub struct RevokedTokens {
+    pub jti: Uuid,
+    pub user_id: Uuid,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+impl RevokedTokens {
+    pub async fn revoke(
+        conn: &BpRecordConn,
+        user_id: Uuid,
+        claims: &Claims,
+    ) -> Result<(), ApiError> {
+        let jti = claims.jti;
+        let expires_at = DateTime::from_timestamp(claims.exp, 0)
+            .unwrap_or_default()
+            .naive_utc();
+        conn.run(move |c| {
+            diesel::delete(
+                revoked_tokens::table.filter(revoked_tokens::expires_at.lt(diesel::dsl::now)),
+            )
+            .execute(c)?;
+            diesel::insert_into(revoked_tokens::table)
+                .values((
+                    revoked_tokens::jti.eq(jti),
+                    revoked_tokens::user_id.eq(user_id),
+                    revoked_tokens::expires_at.eq(expires_at),
+                ))
+                .on_conflict_do_nothing()
+                .execute(c)
+        })
+        .await?;
+        REVOKED.put(jti, true);
+        Ok(())
+    }
+
+    pub async fn revoke_all(conn: &BpRecordConn, user_id: Uuid) -> Result<i32, ApiError> {
+        let account = conn
+            .run(move |c| {
+                diesel::update(users::table.find(user_id))
+                    .set((
+                        users::token_generation.eq(users::token_generation + 1),
+                        users::updated_at.eq(diesel::dsl::now),
+                    ))
+                    .returning((users::token_generation, users::role))
+                    .get_result::<(i32, String)>(c)
+            })
+            .await
+            .map(TokenAccount::new)?;
+        ACCOUNTS.put(user_id, Some(account));
+        Ok(account.generation)
+    }
+
+    /// Rejects every token of an erased or disabled user without waiting for
+    /// the cache.
+    pub fn forget(user_id: Uuid) {
+        ACCOUNTS.put(user_id, None);
+    }
+
+    /// Accepts tokens of a re-enabled user again without waiting for the cache.
+    pub fn restore(user_id: Uuid, generation: i32, role: String) {
+        ACCOUNTS.put(user_id, Some(TokenAccount::new((generation, role))));
+    }
+
+    pub async fn generation(conn: &BpRecordConn, user_id: Uuid) -> Result<Option<i32>, ApiError> {
+        let account = RevokedTokens::account(conn, user_id).await?;
+        Ok(account.map(|account| account.generation))
+    }
+
+    async fn account(conn: &BpRecordConn, user_id: Uuid) -> Result<Option<TokenAccount>, ApiError> {
+        if let Some(account) = ACCOUNTS.get(&user_id) {
+            return Ok(account);
+        }
+        let account = conn
+            .run(move |c| {
+                users::table
+                    .find(user_id)
+                    .filter(users::disabled_at.is_null())
+                    .select((users::token_generation, users::role))
+                    .get_result::<(i32, String)>(c)
+                    .optional()
+            })
+            .await?
+            .map(TokenAccount::new);
+        ACCOUNTS.put(user_id, account);
+        Ok(account)
+    }
+
+    /// Answers `check` from the caches alone, or `None` when the database has
+    /// to be asked.
+    pub fn cached(user_id: Uuid, claims: &Claims) -> Option<Option<TokenAccount>> {
+        let account = ACCOUNTS.get(&user_id)?;
+        let Some(account) = account.filter(|account| claims.generation >= account.generation)
+        else {
+            return Some(None);
+        };
+        let revoked = REVOKED.get(&claims.jti)?;
+        Some((!revoked).then_some(account))
+    }
+
+    /// The account that `claims` was issued to, or `None` when the token has
+    /// been revoked.
+    pub async fn check(
+        conn: &BpRecordConn,
+        user_id: Uuid,
+        claims: &Claims,
+    ) -> Result<Option<TokenAccount>, ApiError> {
+        let account = RevokedTokens::account(conn, user_id).await?;
+        let Some(account) = account.filter(|account| claims.generation >= account.generation)
+        else {
+            return Ok(None);
+        };
+        let jti = claims.jti;
+        if let Some(revoked) = REVOKED.get(&jti) {
+            return Ok((!revoked).then_some(account));
+        }
+        let revoked = conn
+            .run(move |c| {
+                diesel::select(diesel::dsl::exists(revoked_tokens::table.find(jti)))
+                    .get_result::<bool>(c)
+            })
+            .await?;
+        REVOKED.put(jti, revoked);
+        Ok((!revoked).then_some(account))
+    }
+}
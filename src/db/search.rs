@@ -0,0 +1,143 @@
+use crate::db::BpRecordConn;
+use crate::db::member::Members;
+use crate::db::record::{ArchivedRecords, Records};
+use crate::error::api::ApiError;
+use crate::schema::sql_types::{Tsquery, Tsvector};
+use crate::schema::{members, records, records_archive, user_member};
+use diesel::prelude::*;
+use diesel::sql_types::{Nullable, Text};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::env;
+use uuid::Uuid;
+
+lazy_static! {
+    pub static ref SEARCH_CONFIG: String =
+        env::var("SEARCH_CONFIG").unwrap_or_else(|_| "simple".to_owned());
+    pub static ref SEARCH_LIMIT: i64 = {
+        env::var("SEARCH_LIMIT")
+            .unwrap_or_else(|_| "50".to_owned())
+            .parse::<i64>()
+            .unwrap()
+    };
+}
+
+define_sql_function!(fn bp_search_vector(config: Text, document: Nullable<Text>) -> Tsvector);
+define_sql_function!(fn bp_search_query(config: Text, query: Text) -> Tsquery);
+define_sql_function!(fn ts_rank(vector: Tsvector, query: Tsquery) -> Float4);
+
+diesel::infix_operator!(Matches, " @@ ", backend: diesel::pg::Pg);
+
+pub fn search_vector(document: Option<String>) -> bp_search_vector<String, Option<String>> {
+    bp_search_vector(SEARCH_CONFIG.clone(), document)
+}
+
+/// Rebuilds the search vectors that don't match `SEARCH_CONFIG`, covering
+/// rows written before the column existed or under a different config.
+pub async fn reindex(conn: &BpRecordConn) -> Result<usize, ApiError> {
+    let num = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                let member_vector = bp_search_vector(SEARCH_CONFIG.clone(), members::memo);
+                let member_num = diesel::update(
+                    members::table
+                        .filter(members::search_vector.is_distinct_from(member_vector.clone())),
+                )
+                .set(members::search_vector.eq(member_vector))
+                .execute(c)?;
+                let record_vector = bp_search_vector(SEARCH_CONFIG.clone(), records::note);
+                let record_num = diesel::update(
+                    records::table
+                        .filter(records::search_vector.is_distinct_from(record_vector.clone())),
+                )
+                .set(records::search_vector.eq(record_vector))
+                .execute(c)?;
+                let archive_vector = bp_search_vector(SEARCH_CONFIG.clone(), records_archive::note);
+                let archive_num = diesel::update(records_archive::table.filter(
+                    records_archive::search_vector.is_distinct_from(archive_vector.clone()),
+                ))
+                .set(records_archive::search_vector.eq(archive_vector))
+                .execute(c)?;
+                Ok::<usize, diesel::result::Error>(member_num + record_num + archive_num)
+            })
+        })
+        .await?;
+    Ok(num)
+}
+
+pub fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        if matches!(ch, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResult {
+    pub members: Vec<Members>,
+    pub records: Vec<Records>,
+    pub archived_records: Vec<ArchivedRecords>,
+}
+
+impl SearchResult {
+    pub async fn search(
+        conn: &BpRecordConn,
+        user_id: Uuid,
+        keyword: String,
+    ) -> Result<SearchResult, ApiError> {
+        let keyword = keyword.trim().to_owned();
+        if keyword.is_empty() {
+            return Err(ApiError::BadRequest(String::from("搜索内容不能为空")));
+        }
+        let result = conn
+            .run(move |c| {
+                let query = bp_search_query(SEARCH_CONFIG.clone(), keyword);
+                let member_list = members::table
+                    .inner_join(user_member::table)
+                    .filter(user_member::user_id.eq(user_id))
+                    .filter(Matches::new(members::search_vector, query.clone()))
+                    .order(ts_rank(members::search_vector, query.clone()).desc())
+                    .limit(*SEARCH_LIMIT)
+                    .select(Members::as_select())
+                    .get_results::<Members>(c)?;
+                let record_list = records::table
+                    .inner_join(members::table.inner_join(user_member::table))
+                    .filter(user_member::user_id.eq(user_id))
+                    .filter(Matches::new(records::search_vector, query.clone()))
+                    .order((
+                        ts_rank(records::search_vector, query.clone()).desc(),
+                        records::record_at.desc(),
+                    ))
+                    .limit(*SEARCH_LIMIT)
+                    .select(Records::as_select())
+                    .get_results::<Records>(c)?;
+                let archived_list = records_archive::table
+                    .filter(
+                        records_archive::member_id.eq_any(
+                            user_member::table
+                                .filter(user_member::user_id.eq(user_id))
+                                .select(user_member::member_id),
+                        ),
+                    )
+                    .filter(Matches::new(records_archive::search_vector, query.clone()))
+                    .order((
+                        ts_rank(records_archive::search_vector, query).desc(),
+                        records_archive::record_at.desc(),
+                    ))
+                    .limit(*SEARCH_LIMIT)
+                    .select(ArchivedRecords::as_select())
+                    .get_results::<ArchivedRecords>(c)?;
+                Ok::<SearchResult, diesel::result::Error>(SearchResult {
+                    members: member_list,
+                    records: record_list,
+                    archived_records: archived_list,
+                })
+            })
+            .await?;
+        Ok(result)
+    }
+}
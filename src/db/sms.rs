@@ -0,0 +1,128 @@
+use crate::db::BpRecordConn;
+use crate::error::api::ApiError;
+use crate::schema::sms_codes;
+use crate::util::secret::{hash_secret, random_digits};
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::{Queryable, Selectable};
+use lazy_static::lazy_static;
+use std::env;
+use uuid::Uuid;
+
+lazy_static! {
+    pub static ref SMS_CODE_EXPIRE: i64 = {
+        env::var("SMS_CODE_EXPIRE")
+            .unwrap_or_else(|_| "300".to_owned())
+            .parse::<i64>()
+            .unwrap()
+    };
+    pub static ref SMS_CODE_MAX_ATTEMPTS: i32 = {
+        env::var("SMS_CODE_MAX_ATTEMPTS")
+            .unwrap_or_else(|_| "5".to_owned())
+            .parse::<i32>()
+            .unwrap()
+    };
+}
+
+const CODE_LENGTH: usize = 6;
+
+#[derive(Debug, Identifiable, Queryable, Selectable)]
+#[diesel(
+    table_name = crate::schema::sms_codes,
+    primary_key(id),
+    check_for_backend(diesel::pg::Pg),
+)]
+pub struct SmsCodes {
+    pub id: Uuid,
+    pub phone: String,
+    pub code_hash: String,
+    pub expires_at: NaiveDateTime,
+    pub attempts: i32,
+    pub consumed_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// Codes are only six digits, so the hash is salted with the phone number to
+/// keep a leaked table from being reversed with one precomputed lookup.
+fn hash_code(phone: &str, code: &str) -> String {
+    hash_secret(&format!("{}:{}", phone, code))
+}
+
+impl SmsCodes {
+    /// Issues a new code for `phone`, invalidating any code sent earlier.
+    pub async fn insert(conn: &BpRecordConn, phone: String) -> Result<String, ApiError> {
+        let code = random_digits(CODE_LENGTH);
+        let code_hash = hash_code(&phone, &code);
+        let expires_at = Utc::now().naive_utc() + Duration::seconds(*SMS_CODE_EXPIRE);
+        conn.run(move |c| {
+            c.transaction(|x| {
+                diesel::update(
+                    sms_codes::table
+                        .filter(sms_codes::phone.eq(&phone))
+                        .filter(sms_codes::consumed_at.is_null()),
+                )
+                .set((
+                    sms_codes::consumed_at.eq(diesel::dsl::now),
+                    sms_codes::updated_at.eq(diesel::dsl::now),
+                ))
+                .execute(x)?;
+                diesel::insert_into(sms_codes::table)
+                    .values((
+                        sms_codes::phone.eq(phone),
+                        sms_codes::code_hash.eq(code_hash),
+                        sms_codes::expires_at.eq(expires_at),
+                    ))
+                    .execute(x)
+            })
+        })
+        .await?;
+        Ok(code)
+    }
+
+    pub async fn verify(conn: &BpRecordConn, phone: String, code: String) -> Result<(), ApiError> {
+        let code_hash = hash_code(&phone, code.trim());
+        let verified = conn
+            .run(move |c| {
+                c.transaction(|x| {
+                    let sms_code = sms_codes::table
+                        .filter(sms_codes::phone.eq(phone))
+                        .filter(sms_codes::consumed_at.is_null())
+                        .order(sms_codes::created_at.desc())
+                        .for_update()
+                        .first::<SmsCodes>(x)
+                        .optional()?;
+                    let Some(sms_code) = sms_code else {
+                        return Ok(false);
+                    };
+                    if sms_code.expires_at <= Utc::now().naive_utc()
+                        || sms_code.attempts >= *SMS_CODE_MAX_ATTEMPTS
+                    {
+                        return Ok(false);
+                    }
+                    if sms_code.code_hash != code_hash {
+                        diesel::update(sms_codes::table.find(sms_code.id))
+                            .set((
+                                sms_codes::attempts.eq(sms_codes::attempts + 1),
+                                sms_codes::updated_at.eq(diesel::dsl::now),
+                            ))
+                            .execute(x)?;
+                        return Ok(false);
+                    }
+                    diesel::update(sms_codes::table.find(sms_code.id))
+                        .set((
+                            sms_codes::consumed_at.eq(diesel::dsl::now),
+                            sms_codes::updated_at.eq(diesel::dsl::now),
+                        ))
+                        .execute(x)?;
+                    Ok::<bool, diesel::result::Error>(true)
+                })
+            })
+            .await?;
+        if verified {
+            Ok(())
+        } else {
+            Err(ApiError::BadRequest(String::from("验证码错误或已失效")))
+        }
+    }
+}
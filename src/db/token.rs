@@ -0,0 +1,167 @@
+use crate::db::BpRecordConn;
+use crate::error::api::ApiError;
+use crate::error::auth::AuthError;
+use crate::schema::refresh_tokens;
+use crate::util::secret::{hash_secret, random_token};
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::{PgConnection, Queryable, Selectable};
+use lazy_static::lazy_static;
+use std::env;
+use uuid::Uuid;
+
+lazy_static! {
+    pub static ref REFRESH_TOKEN_EXPIRE: i64 = {
+        env::var("REFRESH_TOKEN_EXPIRE")
+            .unwrap_or_else(|_| "2592000".to_owned())
+            .parse::<i64>()
+            .unwrap()
+    };
+}
+
+const TOKEN_LENGTH: usize = 48;
+
+#[derive(Debug, Identifiable, Queryable, Selectable)]
+#[diesel(
+    table_name = crate::schema::refresh_tokens,
+    primary_key(id),
+    check_for_backend(diesel::pg::Pg),
+)]
+pub struct RefreshTokens {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub family_id: Uuid,
+    pub token_hash: String,
+    pub expires_at: NaiveDateTime,
+    pub replaced_by: Option<Uuid>,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+pub struct RefreshToken {
+    pub token: String,
+    pub user_id: Uuid,
+}
+
+impl RefreshTokens {
+    pub async fn insert(conn: &BpRecordConn, user_id: Uuid) -> Result<String, ApiError> {
+        let token = random_token(TOKEN_LENGTH);
+        let token_hash = hash_secret(&token);
+        conn.run(move |c| RefreshTokens::create(c, user_id, Uuid::new_v4(), token_hash))
+            .await?;
+        Ok(token)
+    }
+
+    /// Exchanges a refresh token for a new one in the same family. Presenting a
+    /// token that was already rotated revokes the whole family, since either
+    /// the client or an attacker is holding a stale copy.
+    pub async fn rotate(conn: &BpRecordConn, token: String) -> Result<RefreshToken, ApiError> {
+        let token_hash = hash_secret(token.trim());
+        let token = random_token(TOKEN_LENGTH);
+        let new_hash = hash_secret(&token);
+        let user_id = conn
+            .run(move |c| {
+                c.transaction(|x| {
+                    let current = refresh_tokens::table
+                        .filter(refresh_tokens::token_hash.eq(token_hash))
+                        .for_update()
+                        .get_result::<RefreshTokens>(x)
+                        .optional()?;
+                    let Some(current) = current else {
+                        return Ok(None);
+                    };
+                    if current.revoked_at.is_some() {
+                        if current.replaced_by.is_some() {
+                            warn!("refresh token reuse detected, user: {}", current.user_id);
+                            RefreshTokens::revoke_family(x, current.family_id)?;
+                        }
+                        return Ok(None);
+                    }
+                    if current.expires_at <= Utc::now().naive_utc() {
+                        return Ok(None);
+                    }
+                    let next =
+                        RefreshTokens::create(x, current.user_id, current.family_id, new_hash)?;
+                    diesel::update(refresh_tokens::table.find(current.id))
+                        .set((
+                            refresh_tokens::replaced_by.eq(next.id),
+                            refresh_tokens::revoked_at.eq(diesel::dsl::now),
+                            refresh_tokens::updated_at.eq(diesel::dsl::now),
+                        ))
+                        .execute(x)?;
+                    Ok::<Option<Uuid>, diesel::result::Error>(Some(current.user_id))
+                })
+            })
+            .await?;
+        let user_id = user_id.ok_or(AuthError::InvalidToken)?;
+        Ok(RefreshToken { token, user_id })
+    }
+
+    pub async fn revoke(conn: &BpRecordConn, user_id: Uuid, token: String) -> Result<(), ApiError> {
+        let token_hash = hash_secret(token.trim());
+        conn.run(move |c| {
+            c.transaction(|x| {
+                let current = refresh_tokens::table
+                    .filter(refresh_tokens::token_hash.eq(token_hash))
+                    .filter(refresh_tokens::user_id.eq(user_id))
+                    .get_result::<RefreshTokens>(x)
+                    .optional()?;
+                if let Some(current) = current {
+                    RefreshTokens::revoke_family(x, current.family_id)?;
+                }
+                Ok::<(), diesel::result::Error>(())
+            })
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub async fn revoke_all(conn: &BpRecordConn, user_id: Uuid) -> Result<usize, ApiError> {
+        let num = conn
+            .run(move |c| {
+                diesel::update(
+                    refresh_tokens::table
+                        .filter(refresh_tokens::user_id.eq(user_id))
+                        .filter(refresh_tokens::revoked_at.is_null()),
+                )
+                .set((
+                    refresh_tokens::revoked_at.eq(diesel::dsl::now),
+                    refresh_tokens::updated_at.eq(diesel::dsl::now),
+                ))
+                .execute(c)
+            })
+            .await?;
+        Ok(num)
+    }
+
+    fn create(
+        x: &mut PgConnection,
+        user_id: Uuid,
+        family_id: Uuid,
+        token_hash: String,
+    ) -> QueryResult<RefreshTokens> {
+        let expires_at = Utc::now().naive_utc() + Duration::seconds(*REFRESH_TOKEN_EXPIRE);
+        diesel::insert_into(refresh_tokens::table)
+            .values((
+                refresh_tokens::user_id.eq(user_id),
+                refresh_tokens::family_id.eq(family_id),
+                refresh_tokens::token_hash.eq(token_hash),
+                refresh_tokens::expires_at.eq(expires_at),
+            ))
+            .get_result::<RefreshTokens>(x)
+    }
+
+    fn revoke_family(x: &mut PgConnection, family_id: Uuid) -> QueryResult<usize> {
+        diesel::update(
+            refresh_tokens::table
+                .filter(refresh_tokens::family_id.eq(family_id))
+                .filter(refresh_tokens::revoked_at.is_null()),
+        )
+        .set((
+            refresh_tokens::revoked_at.eq(diesel::dsl::now),
+            refresh_tokens::updated_at.eq(diesel::dsl::now),
+        ))
+        .execute(x)
+    }
+}
@@ -1,11 +1,12 @@
 use crate::db::BpRecordConn;
 use crate::error::api::ApiError;
-use crate::schema::users;
+use crate::schema::{user_identities, users};
+use crate::util::cipher::{Cipher, SESSION_KEY_CIPHER};
 use crate::util::serde_time_format;
 use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use diesel::{Queryable, Selectable};
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
 use uuid::Uuid;
 
 #[derive(Debug, Serialize, Identifiable, Queryable, Selectable)]
@@ -16,30 +17,39 @@ use uuid::Uuid;
 )]
 pub struct Users {
     pub id: Uuid,
-    pub openid: String,
-    pub session_key: String,
+    #[serde(skip_serializing)]
+    pub session_key: Option<String>,
     #[serde(with = "serde_time_format")]
     pub created_at: NaiveDateTime,
     #[serde(with = "serde_time_format")]
     pub updated_at: NaiveDateTime,
+    #[serde(skip_serializing)]
+    pub token_generation: i32,
+    pub nickname: Option<String>,
+    pub avatar_url: Option<String>,
+    pub phone: Option<String>,
+    #[serde(skip_serializing)]
+    pub unionid: Option<String>,
+    pub role: String,
+    #[serde(with = "serde_time_format::optional")]
+    pub disabled_at: Option<NaiveDateTime>,
 }
 
-#[derive(Debug, FromForm)]
-pub struct UserQuery {
-    #[field(name = "openid")]
-    pub openid: Option<String>,
-    #[field(name = "session_key")]
-    pub session_key: Option<String>,
+/// What a user may see about their own account; never carries credentials.
+#[derive(Debug, Serialize)]
+pub struct UserDetail {
+    pub id: Uuid,
+    pub nickname: Option<String>,
+    pub avatar_url: Option<String>,
+    #[serde(with = "serde_time_format")]
+    pub created_at: NaiveDateTime,
+    pub identities: Vec<String>,
 }
 
-#[derive(Insertable, Deserialize)]
-#[diesel(
-    table_name = crate::schema::users,
-    check_for_backend(diesel::pg::Pg),
-)]
-pub struct NewUser {
-    pub openid: String,
-    pub session_key: String,
+pub fn seal_session_key(session_key: Option<String>) -> Result<Option<String>, ApiError> {
+    session_key
+        .map(|session_key| SESSION_KEY_CIPHER.seal(&session_key))
+        .transpose()
 }
 
 impl Users {
@@ -50,45 +60,115 @@ impl Users {
         Ok(user)
     }
 
-    pub async fn select(
-        conn: &BpRecordConn,
-        query: Option<UserQuery>,
-    ) -> Result<Vec<Users>, ApiError> {
-        let mut user_query = users::table.into_boxed();
-        if let Some(query) = query {
-            if let Some(openid) = query.openid {
-                user_query = user_query.filter(users::openid.eq(openid));
-            }
-            if let Some(session_key) = query.session_key {
-                user_query = user_query.filter(users::session_key.eq(session_key));
-            }
+    pub async fn profile(conn: &BpRecordConn, id: Uuid) -> Result<UserDetail, ApiError> {
+        let (user, identities) = conn
+            .run(move |c| {
+                let user = users::table.find(id).get_result::<Users>(c)?;
+                let identities = user_identities::table
+                    .filter(user_identities::user_id.eq(id))
+                    .select(user_identities::provider)
+                    .distinct()
+                    .order(user_identities::provider.asc())
+                    .get_results::<String>(c)?;
+                Ok::<(Users, Vec<String>), diesel::result::Error>((user, identities))
+            })
+            .await?;
+        Ok(UserDetail {
+            id: user.id,
+            nickname: user.nickname,
+            avatar_url: user.avatar_url,
+            created_at: user.created_at,
+            identities,
+        })
+    }
+
+    /// Returns the decrypted WeChat `session_key`, if the user has one.
+    pub async fn session_key(conn: &BpRecordConn, id: Uuid) -> Result<Option<String>, ApiError> {
+        let session_key = conn
+            .run(move |c| {
+                users::table
+                    .find(id)
+                    .select(users::session_key)
+                    .get_result::<Option<String>>(c)
+            })
+            .await?;
+        session_key
+            .map(|session_key| SESSION_KEY_CIPHER.open(&session_key))
+            .transpose()
+    }
+
+    /// Seals plaintext session keys left from before encryption at rest and
+    /// moves keys sealed under a retired key to the current one.
+    pub async fn reseal_session_keys(conn: &BpRecordConn) -> Result<usize, ApiError> {
+        let current = format!("{}%", SESSION_KEY_CIPHER.current_prefix());
+        let session_keys = conn
+            .run(move |c| {
+                users::table
+                    .filter(users::session_key.not_like(current))
+                    .select((users::id, users::session_key.assume_not_null()))
+                    .get_results::<(Uuid, String)>(c)
+            })
+            .await?;
+        let mut resealed = Vec::new();
+        for (id, session_key) in session_keys {
+            let plain = if Cipher::is_sealed(&session_key) {
+                SESSION_KEY_CIPHER.open(&session_key)?
+            } else {
+                session_key.clone()
+            };
+            resealed.push((id, session_key, SESSION_KEY_CIPHER.seal(&plain)?));
         }
-        let users_list = conn.run(|c| user_query.get_results::<Users>(c)).await?;
-        Ok(users_list)
+        let num = conn
+            .run(move |c| {
+                c.transaction(|x| {
+                    let mut num = 0;
+                    for (id, session_key, sealed) in resealed {
+                        // Skip rows refreshed by a sign-in since they were read.
+                        num += diesel::update(
+                            users::table
+                                .find(id)
+                                .filter(users::session_key.eq(session_key)),
+                        )
+                        .set(users::session_key.eq(sealed))
+                        .execute(x)?;
+                    }
+                    Ok::<usize, diesel::result::Error>(num)
+                })
+            })
+            .await?;
+        Ok(num)
     }
 
-    pub async fn insert(conn: &BpRecordConn, new_user: NewUser) -> Result<Users, ApiError> {
+    pub async fn update_phone(
+        conn: &BpRecordConn,
+        id: Uuid,
+        phone: String,
+    ) -> Result<Users, ApiError> {
         let user = conn
             .run(move |c| {
-                diesel::insert_into(users::table)
-                    .values(new_user)
+                diesel::update(users::table.find(id))
+                    .set((
+                        users::phone.eq(phone),
+                        users::updated_at.eq(diesel::dsl::now),
+                    ))
                     .get_result::<Users>(c)
             })
             .await?;
         Ok(user)
     }
 
-    pub async fn update(
+    pub async fn update_profile(
         conn: &BpRecordConn,
         id: Uuid,
-        new_user: NewUser,
+        nickname: String,
+        avatar_url: Option<String>,
     ) -> Result<Users, ApiError> {
         let user = conn
             .run(move |c| {
                 diesel::update(users::table.find(id))
                     .set((
-                        users::openid.eq(new_user.openid),
-                        users::session_key.eq(new_user.session_key),
+                        users::nickname.eq(nickname),
+                        users::avatar_url.eq(avatar_url),
                         users::updated_at.eq(diesel::dsl::now),
                     ))
                     .get_result::<Users>(c)
@@ -96,11 +176,4 @@ impl Users {
             .await?;
         Ok(user)
     }
-
-    pub async fn delete(conn: &BpRecordConn, id: Uuid) -> Result<usize, ApiError> {
-        let result = conn
-            .run(move |c| diesel::delete(users::table.find(id)).execute(c))
-            .await?;
-        Ok(result)
-    }
 }
@@ -9,6 +9,8 @@ pub enum ApiError {
     NotFound,
     Auth(AuthError),
     BadRequest(String),
+    Forbidden(String),
+    TooManyRequests(String),
     Internal(anyhow::Error),
 }
 
@@ -40,6 +42,16 @@ impl<'r> Responder<'r, 'static> for ApiError {
                 .status(Status::BadRequest)
                 .sized_body(err.len(), Cursor::new(err))
                 .ok(),
+            ApiError::Forbidden(err) => Response::build()
+                .header(ContentType::JSON)
+                .status(Status::Forbidden)
+                .sized_body(err.len(), Cursor::new(err))
+                .ok(),
+            ApiError::TooManyRequests(err) => Response::build()
+                .header(ContentType::JSON)
+                .status(Status::TooManyRequests)
+                .sized_body(err.len(), Cursor::new(err))
+                .ok(),
             ApiError::Internal(err) => Response::build()
                 .header(ContentType::JSON)
                 .status(Status::InternalServerError)
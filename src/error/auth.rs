@@ -3,12 +3,28 @@ use rocket::response::Responder;
 use rocket::{Request, Response};
 use std::io::Cursor;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum AuthError {
     WrongCredentials,
     MissingCredentials,
     TokenCreation,
     InvalidToken,
+    MalformedToken,
+    ExpiredToken,
+    ImmatureToken,
+    InvalidSignature,
+    UnknownKey,
+    InvalidIssuer,
+    InvalidAudience,
+    InvalidSubject,
+    RevokedToken,
+    InvalidCode,
+    CodeUsed,
+    UserBlocked,
+    UserDisabled,
+    AdminRequired,
+    ProviderRateLimited,
+    ProviderUnavailable,
 }
 
 impl From<AuthError> for (Status, AuthError) {
@@ -18,6 +34,26 @@ impl From<AuthError> for (Status, AuthError) {
             AuthError::MissingCredentials => (Status::Unauthorized, AuthError::MissingCredentials),
             AuthError::TokenCreation => (Status::InternalServerError, AuthError::TokenCreation),
             AuthError::InvalidToken => (Status::Unauthorized, AuthError::InvalidToken),
+            AuthError::MalformedToken => (Status::Unauthorized, AuthError::MalformedToken),
+            AuthError::ExpiredToken => (Status::Unauthorized, AuthError::ExpiredToken),
+            AuthError::ImmatureToken => (Status::Unauthorized, AuthError::ImmatureToken),
+            AuthError::InvalidSignature => (Status::Unauthorized, AuthError::InvalidSignature),
+            AuthError::UnknownKey => (Status::Unauthorized, AuthError::UnknownKey),
+            AuthError::InvalidIssuer => (Status::Unauthorized, AuthError::InvalidIssuer),
+            AuthError::InvalidAudience => (Status::Unauthorized, AuthError::InvalidAudience),
+            AuthError::InvalidSubject => (Status::Unauthorized, AuthError::InvalidSubject),
+            AuthError::RevokedToken => (Status::Unauthorized, AuthError::RevokedToken),
+            AuthError::InvalidCode => (Status::Unauthorized, AuthError::InvalidCode),
+            AuthError::CodeUsed => (Status::Unauthorized, AuthError::CodeUsed),
+            AuthError::UserBlocked => (Status::Forbidden, AuthError::UserBlocked),
+            AuthError::UserDisabled => (Status::Forbidden, AuthError::UserDisabled),
+            AuthError::AdminRequired => (Status::Forbidden, AuthError::AdminRequired),
+            AuthError::ProviderRateLimited => {
+                (Status::TooManyRequests, AuthError::ProviderRateLimited)
+            }
+            AuthError::ProviderUnavailable => {
+                (Status::ServiceUnavailable, AuthError::ProviderUnavailable)
+            }
         }
     }
 }
@@ -29,6 +65,26 @@ impl<'r> Responder<'r, 'static> for AuthError {
             AuthError::MissingCredentials => (Status::Unauthorized, "Missing credentials"),
             AuthError::TokenCreation => (Status::InternalServerError, "Token creation error"),
             AuthError::InvalidToken => (Status::Unauthorized, "Invalid token"),
+            AuthError::MalformedToken => (Status::Unauthorized, "Malformed token"),
+            AuthError::ExpiredToken => (Status::Unauthorized, "Token expired"),
+            AuthError::ImmatureToken => (Status::Unauthorized, "Token not yet valid"),
+            AuthError::InvalidSignature => (Status::Unauthorized, "Invalid token signature"),
+            AuthError::UnknownKey => (Status::Unauthorized, "Unknown signing key"),
+            AuthError::InvalidIssuer => (Status::Unauthorized, "Invalid token issuer"),
+            AuthError::InvalidAudience => (Status::Unauthorized, "Invalid token audience"),
+            AuthError::InvalidSubject => (Status::Unauthorized, "Invalid token subject"),
+            AuthError::RevokedToken => (Status::Unauthorized, "Token revoked"),
+            AuthError::InvalidCode => (Status::Unauthorized, "Invalid login code"),
+            AuthError::CodeUsed => (Status::Unauthorized, "Login code already used"),
+            AuthError::UserBlocked => (Status::Forbidden, "User blocked by identity provider"),
+            AuthError::UserDisabled => (Status::Forbidden, "User disabled"),
+            AuthError::AdminRequired => (Status::Forbidden, "Admin role required"),
+            AuthError::ProviderRateLimited => {
+                (Status::TooManyRequests, "Identity provider rate limited")
+            }
+            AuthError::ProviderUnavailable => {
+                (Status::ServiceUnavailable, "Identity provider unavailable")
+            }
         };
         Response::build()
             .header(ContentType::JSON)
@@ -37,3 +93,17 @@ impl<'r> Responder<'r, 'static> for AuthError {
             .ok()
     }
 }
+
+#[catch(401)]
+pub fn unauthorized(request: &Request<'_>) -> AuthError {
+    request
+        .local_cache(|| None::<AuthError>)
+        .unwrap_or(AuthError::MissingCredentials)
+}
+
+#[catch(403)]
+pub fn forbidden(request: &Request<'_>) -> AuthError {
+    request
+        .local_cache(|| None::<AuthError>)
+        .unwrap_or(AuthError::AdminRequired)
+}
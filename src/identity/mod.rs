@@ -0,0 +1,32 @@
+use crate::db::BpRecordConn;
+use crate::error::api::ApiError;
+
+pub mod password;
+pub mod sms;
+pub mod wechat;
+
+pub const WECHAT: &str = "wechat";
+pub const PASSWORD: &str = "password";
+pub const PHONE: &str = "phone";
+
+/// An external account proven by an identity provider. `subject` is stable
+/// for the lifetime of that account, e.g. a WeChat openid or a username.
+/// `unionid` is shared by all apps of one WeChat open platform account.
+pub struct Identity {
+    pub provider: &'static str,
+    pub subject: String,
+    pub credential: Option<String>,
+    pub session_key: Option<String>,
+    pub unionid: Option<String>,
+}
+
+#[rocket::async_trait]
+pub trait IdentityProvider: Send + Sync {
+    type Credentials: Send;
+
+    async fn authenticate(
+        &self,
+        conn: &BpRecordConn,
+        credentials: Self::Credentials,
+    ) -> Result<Identity, ApiError>;
+}
@@ -0,0 +1,117 @@
+use crate::db::BpRecordConn;
+use crate::db::identity::UserIdentities;
+use crate::error::api::ApiError;
+use crate::error::auth::AuthError;
+use crate::identity::{Identity, IdentityProvider, PASSWORD};
+use anyhow::anyhow;
+use argon2::Argon2;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use lazy_static::lazy_static;
+use rocket::tokio::task;
+use serde::Deserialize;
+
+const USERNAME_MIN_LENGTH: usize = 3;
+const USERNAME_MAX_LENGTH: usize = 32;
+const PASSWORD_MIN_LENGTH: usize = 8;
+
+lazy_static! {
+    /// Verified against when the username is unknown, so that a miss costs
+    /// the same argon2 run as a wrong password and does not reveal which
+    /// usernames exist.
+    pub static ref DUMMY_HASH: String = hash(&rand::random::<[u8; 16]>()).unwrap();
+}
+
+fn hash(password: &[u8]) -> anyhow::Result<String> {
+    let salt = SaltString::encode_b64(&rand::random::<[u8; 16]>()).map_err(|err| anyhow!(err))?;
+    Argon2::default()
+        .hash_password(password, &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|err| anyhow!(err))
+}
+
+#[derive(Deserialize)]
+pub struct PasswordCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl PasswordCredentials {
+    fn username(&self) -> String {
+        self.username.trim().to_lowercase()
+    }
+
+    pub fn validate(&self) -> Result<(), ApiError> {
+        let username = self.username();
+        if !(USERNAME_MIN_LENGTH..=USERNAME_MAX_LENGTH).contains(&username.len())
+            || !username
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+        {
+            return Err(ApiError::BadRequest(format!(
+                "用户名须为{}到{}位字母、数字或下划线",
+                USERNAME_MIN_LENGTH, USERNAME_MAX_LENGTH
+            )));
+        }
+        if self.password.chars().count() < PASSWORD_MIN_LENGTH {
+            return Err(ApiError::BadRequest(format!(
+                "密码不能少于{}位",
+                PASSWORD_MIN_LENGTH
+            )));
+        }
+        Ok(())
+    }
+}
+
+pub struct PasswordProvider;
+
+impl PasswordProvider {
+    /// Hashes the password of a new username/password identity.
+    pub async fn register(&self, credentials: PasswordCredentials) -> Result<Identity, ApiError> {
+        credentials.validate()?;
+        let subject = credentials.username();
+        let credential =
+            task::spawn_blocking(move || hash(credentials.password.as_bytes())).await??;
+        Ok(Identity {
+            provider: PASSWORD,
+            subject,
+            credential: Some(credential),
+            session_key: None,
+            unionid: None,
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl IdentityProvider for PasswordProvider {
+    type Credentials = PasswordCredentials;
+
+    async fn authenticate(
+        &self,
+        conn: &BpRecordConn,
+        credentials: Self::Credentials,
+    ) -> Result<Identity, ApiError> {
+        let subject = credentials.username();
+        let identity = UserIdentities::find(conn, PASSWORD, subject.clone()).await?;
+        let credential = identity.and_then(|identity| identity.credential);
+        let known = credential.is_some();
+        let verified = task::spawn_blocking(move || {
+            let credential = credential.as_deref().unwrap_or(DUMMY_HASH.as_str());
+            PasswordHash::new(credential).is_ok_and(|hash| {
+                Argon2::default()
+                    .verify_password(credentials.password.as_bytes(), &hash)
+                    .is_ok()
+            })
+        })
+        .await?;
+        if !known || !verified {
+            return Err(AuthError::WrongCredentials.into());
+        }
+        Ok(Identity {
+            provider: PASSWORD,
+            subject,
+            credential: None,
+            session_key: None,
+            unionid: None,
+        })
+    }
+}
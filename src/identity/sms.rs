@@ -0,0 +1,49 @@
+use crate::db::BpRecordConn;
+use crate::db::sms::SmsCodes;
+use crate::error::api::ApiError;
+use crate::identity::{Identity, IdentityProvider, PHONE};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct SmsCredentials {
+    pub phone: String,
+    pub code: String,
+}
+
+/// Accepts mainland China mobile numbers, with or without the `+86` prefix,
+/// and returns the bare 11-digit form used as the identity subject.
+pub fn normalize_phone(phone: &str) -> Result<String, ApiError> {
+    let phone = phone.trim().replace([' ', '-'], "");
+    let phone = phone
+        .strip_prefix("+86")
+        .or_else(|| phone.strip_prefix("86").filter(|phone| phone.len() == 11))
+        .unwrap_or(&phone);
+    if phone.len() != 11 || !phone.starts_with('1') || !phone.chars().all(|ch| ch.is_ascii_digit())
+    {
+        return Err(ApiError::BadRequest(String::from("手机号格式不正确")));
+    }
+    Ok(phone.to_owned())
+}
+
+pub struct SmsProvider;
+
+#[rocket::async_trait]
+impl IdentityProvider for SmsProvider {
+    type Credentials = SmsCredentials;
+
+    async fn authenticate(
+        &self,
+        conn: &BpRecordConn,
+        credentials: Self::Credentials,
+    ) -> Result<Identity, ApiError> {
+        let phone = normalize_phone(&credentials.phone)?;
+        SmsCodes::verify(conn, phone.clone(), credentials.code).await?;
+        Ok(Identity {
+            provider: PHONE,
+            subject: phone,
+            credential: None,
+            session_key: None,
+            unionid: None,
+        })
+    }
+}
@@ -0,0 +1,26 @@
+use crate::db::BpRecordConn;
+use crate::error::api::ApiError;
+use crate::identity::{Identity, IdentityProvider, WECHAT};
+use crate::wechat::WechatClient;
+
+pub struct WechatProvider<'a>(pub &'a WechatClient);
+
+#[rocket::async_trait]
+impl IdentityProvider for WechatProvider<'_> {
+    type Credentials = String;
+
+    async fn authenticate(
+        &self,
+        _conn: &BpRecordConn,
+        code: Self::Credentials,
+    ) -> Result<Identity, ApiError> {
+        let wx_user = self.0.code2session(&code).await?;
+        Ok(Identity {
+            provider: WECHAT,
+            subject: wx_user.openid,
+            credential: None,
+            session_key: Some(wx_user.session_key),
+            unionid: wx_user.unionid,
+        })
+    }
+}
@@ -0,0 +1,40 @@
+use crate::db::BpRecordConn;
+use crate::db::record::Records;
+use lazy_static::lazy_static;
+use rocket::fairing::AdHoc;
+use rocket::tokio::time::{Duration, interval};
+use std::env;
+
+lazy_static! {
+    pub static ref RECORD_ARCHIVE_INTERVAL: u64 = {
+        env::var("RECORD_ARCHIVE_INTERVAL")
+            .unwrap_or_else(|_| "86400".to_owned())
+            .parse::<u64>()
+            .unwrap()
+    };
+}
+
+pub fn fairing() -> AdHoc {
+    AdHoc::on_liftoff("Record Archive", |rocket| {
+        Box::pin(async move {
+            let Some(pool) = BpRecordConn::pool(rocket).cloned() else {
+                error!("record archive: database pool is not attached");
+                return;
+            };
+            rocket::tokio::spawn(async move {
+                let mut ticker = interval(Duration::from_secs(*RECORD_ARCHIVE_INTERVAL));
+                loop {
+                    ticker.tick().await;
+                    let Some(conn) = BpRecordConn::from_pool(&pool).await else {
+                        error!("record archive: failed to get database connection");
+                        continue;
+                    };
+                    match Records::archive(&conn).await {
+                        Ok(num) => info!("record archive: archived {} records", num),
+                        Err(err) => error!("record archive: {:?}", err),
+                    }
+                }
+            });
+        })
+    })
+}
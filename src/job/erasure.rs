@@ -0,0 +1,70 @@
+use crate::db::BpRecordConn;
+use crate::db::erasure::{AccountDeletions, AccountErasures};
+use crate::db::revocation::RevokedTokens;
+use crate::storage;
+use lazy_static::lazy_static;
+use rocket::fairing::AdHoc;
+use rocket::tokio::time::{Duration, interval};
+use std::env;
+
+lazy_static! {
+    pub static ref ACCOUNT_ERASURE_INTERVAL: u64 = {
+        env::var("ACCOUNT_ERASURE_INTERVAL")
+            .unwrap_or_else(|_| "3600".to_owned())
+            .parse::<u64>()
+            .unwrap()
+    };
+}
+
+pub fn fairing() -> AdHoc {
+    AdHoc::on_liftoff("Account Erasure", |rocket| {
+        Box::pin(async move {
+            let Some(pool) = BpRecordConn::pool(rocket).cloned() else {
+                error!("account erasure: database pool is not attached");
+                return;
+            };
+            let storage = storage::from_env();
+            let exports = storage::exports_from_env();
+            rocket::tokio::spawn(async move {
+                let mut ticker = interval(Duration::from_secs(*ACCOUNT_ERASURE_INTERVAL));
+                loop {
+                    ticker.tick().await;
+                    let Some(conn) = BpRecordConn::from_pool(&pool).await else {
+                        error!("account erasure: failed to get database connection");
+                        continue;
+                    };
+                    let user_ids = match AccountDeletions::due(&conn).await {
+                        Ok(user_ids) => user_ids,
+                        Err(err) => {
+                            error!("account erasure: {:?}", err);
+                            continue;
+                        }
+                    };
+                    for user_id in user_ids {
+                        match AccountErasures::erase(&conn, user_id).await {
+                            Ok(Some(erasure)) => {
+                                RevokedTokens::forget(user_id);
+                                for key in erasure.avatar_keys {
+                                    if let Err(err) = storage.delete(&key).await {
+                                        error!("account erasure: {:?}", err);
+                                    }
+                                }
+                                for key in erasure.export_keys {
+                                    if let Err(err) = exports.0.delete(&key).await {
+                                        error!("account erasure: {:?}", err);
+                                    }
+                                }
+                                info!(
+                                    "account erasure: erased user {}, {} members, {} records",
+                                    user_id, erasure.audit.member_num, erasure.audit.record_num
+                                );
+                            }
+                            Ok(None) => {}
+                            Err(err) => error!("account erasure: user {}, {:?}", user_id, err),
+                        }
+                    }
+                }
+            });
+        })
+    })
+}
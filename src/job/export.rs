@@ -0,0 +1,148 @@
+use crate::db::export::DataExports;
+use crate::db::identity::UserIdentities;
+use crate::db::member::UserMember;
+use crate::db::profile::UserProfiles;
+use crate::db::record::Records;
+use crate::db::user::Users;
+use crate::db::{BpRecordConn, BpRecordPool};
+use crate::error::api::ApiError;
+use crate::storage::{ExportStorage, Storage};
+use crate::util::timezone;
+use lazy_static::lazy_static;
+use rocket::fairing::AdHoc;
+use rocket::tokio::task;
+use rocket::tokio::time::{Duration, interval};
+use serde::Serialize;
+use std::env;
+use std::io::{Cursor, Write};
+use std::sync::Arc;
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+lazy_static! {
+    pub static ref EXPORT_PURGE_INTERVAL: u64 = {
+        env::var("EXPORT_PURGE_INTERVAL")
+            .unwrap_or_else(|_| "3600".to_owned())
+            .parse::<u64>()
+            .unwrap()
+    };
+}
+
+/// Builds the archive for `export` in the background, so large accounts do
+/// not hold up a request worker. The task takes its own connection from
+/// `pool` rather than keeping the request's.
+pub fn spawn(pool: BpRecordPool, storage: Arc<dyn Storage>, export: &DataExports) {
+    let (id, user_id) = (export.id, export.user_id);
+    rocket::tokio::spawn(async move {
+        let Some(conn) = BpRecordConn::from_pool(&pool).await else {
+            error!(
+                "data export: user {}, failed to get database connection",
+                user_id
+            );
+            return;
+        };
+        let storage_key = match build(&conn, storage.as_ref(), id, user_id).await {
+            Ok(storage_key) => Some(storage_key),
+            Err(err) => {
+                error!("data export: user {}, {:?}", user_id, err);
+                None
+            }
+        };
+        if let Err(err) = DataExports::finish(&conn, id, storage_key).await {
+            error!("data export: user {}, {:?}", user_id, err);
+        }
+    });
+}
+
+/// Fails exports left `pending` by a previous run, whose tasks died with the
+/// process, so their users can request a new one. Then deletes expired
+/// exports and their files every `EXPORT_PURGE_INTERVAL` seconds, starting
+/// at liftoff.
+pub fn fairing() -> AdHoc {
+    AdHoc::on_liftoff("Data Export Cleanup", |rocket| {
+        Box::pin(async move {
+            let Some(pool) = BpRecordConn::pool(rocket).cloned() else {
+                error!("data export: database pool is not attached");
+                return;
+            };
+            let Some(ExportStorage(storage)) = rocket.state::<ExportStorage>() else {
+                error!("data export: export storage is not managed");
+                return;
+            };
+            let storage = storage.clone();
+            let Some(conn) = BpRecordConn::from_pool(&pool).await else {
+                error!("data export: failed to get database connection");
+                return;
+            };
+            match DataExports::fail_pending(&conn).await {
+                Ok(0) => {}
+                Ok(count) => info!("data export: failed {} stale pending exports", count),
+                Err(err) => error!("data export: {:?}", err),
+            }
+            rocket::tokio::spawn(async move {
+                let mut ticker = interval(Duration::from_secs(*EXPORT_PURGE_INTERVAL));
+                loop {
+                    ticker.tick().await;
+                    let Some(conn) = BpRecordConn::from_pool(&pool).await else {
+                        error!("data export: failed to get database connection");
+                        continue;
+                    };
+                    match DataExports::purge_expired(&conn).await {
+                        Ok(storage_keys) => {
+                            for key in storage_keys {
+                                if let Err(err) = storage.delete(&key).await {
+                                    error!("data export: {:?}", err);
+                                }
+                            }
+                        }
+                        Err(err) => error!("data export: {:?}", err),
+                    }
+                }
+            });
+        })
+    })
+}
+
+async fn build(
+    conn: &BpRecordConn,
+    storage: &dyn Storage,
+    id: Uuid,
+    user_id: Uuid,
+) -> Result<String, ApiError> {
+    let user = Users::detail(conn, user_id).await?;
+    let profile = UserProfiles::detail(conn, user_id).await?;
+    let timezone = UserProfiles::timezone(conn, user_id).await?;
+    let identities = UserIdentities::get_user_identities(conn, user_id).await?;
+    let members = UserMember::get_user_members(conn, user_id).await?;
+    let mut records = Vec::new();
+    for member in &members {
+        records.extend(Records::get_full_history(conn, member.member.id).await?);
+    }
+    let files = timezone::scope(Some(timezone), || {
+        Ok::<_, ApiError>(vec![
+            ("user.json", to_json(&user)?),
+            ("profile.json", to_json(&profile)?),
+            ("identities.json", to_json(&identities)?),
+            ("members.json", to_json(&members)?),
+            ("records.json", to_json(&records)?),
+        ])
+    })?;
+    let archive = task::spawn_blocking(move || {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        for (name, bytes) in files {
+            writer.start_file(name, options)?;
+            writer.write_all(&bytes)?;
+        }
+        Ok::<Vec<u8>, anyhow::Error>(writer.finish()?.into_inner())
+    })
+    .await??;
+    let storage_key = format!("{}/{}.zip", user_id, id);
+    storage.put(&storage_key, archive).await?;
+    Ok(storage_key)
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<Vec<u8>, ApiError> {
+    Ok(serde_json::to_vec_pretty(value)?)
+}
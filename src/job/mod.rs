@@ -0,0 +1,6 @@
+pub mod archive;
+pub mod erasure;
+pub mod export;
+pub mod quota_plan;
+pub mod search_index;
+pub mod session_key;
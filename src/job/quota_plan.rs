@@ -0,0 +1,23 @@
+use crate::db::BpRecordConn;
+use crate::db::quota::{MEMBER_NUM, MemberQuota};
+use rocket::fairing::AdHoc;
+
+/// Seeds the default quota plan from `MEMBER_NUM` once the server has started.
+pub fn fairing() -> AdHoc {
+    AdHoc::on_liftoff("Quota Plan Seed", |rocket| {
+        Box::pin(async move {
+            let Some(conn) = BpRecordConn::get_one(rocket).await else {
+                error!("quota plan seed: failed to get database connection");
+                return;
+            };
+            match MemberQuota::seed_default_plan(&conn).await {
+                Ok(0) => {}
+                Ok(_) => info!(
+                    "quota plan seed: created default plan with {} members",
+                    *MEMBER_NUM
+                ),
+                Err(err) => error!("quota plan seed: {:?}", err),
+            }
+        })
+    })
+}
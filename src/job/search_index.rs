@@ -0,0 +1,21 @@
+use crate::db::BpRecordConn;
+use crate::db::search;
+use rocket::fairing::AdHoc;
+
+/// Reindexes member memos and record notes under `SEARCH_CONFIG` once the
+/// server has started.
+pub fn fairing() -> AdHoc {
+    AdHoc::on_liftoff("Search Reindex", |rocket| {
+        Box::pin(async move {
+            let Some(conn) = BpRecordConn::get_one(rocket).await else {
+                error!("search reindex: failed to get database connection");
+                return;
+            };
+            match search::reindex(&conn).await {
+                Ok(0) => {}
+                Ok(num) => info!("search reindex: reindexed {} rows", num),
+                Err(err) => error!("search reindex: {:?}", err),
+            }
+        })
+    })
+}
@@ -0,0 +1,26 @@
+use crate::db::BpRecordConn;
+use crate::db::user::Users;
+use rocket::fairing::AdHoc;
+
+/// Seals session keys stored before encryption at rest, or under a retired
+/// key, before the server starts. Launch is aborted if this fails, so no
+/// server runs with plaintext keys left in the database.
+pub fn fairing() -> AdHoc {
+    AdHoc::try_on_ignite("Session Key Reseal", |rocket| async move {
+        let Some(conn) = BpRecordConn::get_one(&rocket).await else {
+            error!("session key reseal: failed to get database connection");
+            return Err(rocket);
+        };
+        match Users::reseal_session_keys(&conn).await {
+            Ok(0) => Ok(rocket),
+            Ok(num) => {
+                info!("session key reseal: resealed {} session keys", num);
+                Ok(rocket)
+            }
+            Err(err) => {
+                error!("session key reseal: {:?}", err);
+                Err(rocket)
+            }
+        }
+    })
+}
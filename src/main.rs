@@ -1,23 +1,66 @@
 #[macro_use]
 extern crate rocket;
 use crate::db::BpRecordConn;
+use rocket::data::Limits;
+use rocket::fs::FileServer;
 use rocket::launch;
 
 pub mod api;
 pub mod db;
 pub mod error;
+pub mod identity;
+pub mod job;
 pub mod model;
 pub mod schema;
+pub mod sms;
+pub mod storage;
 pub mod util;
+pub mod wechat;
 
 #[launch]
 fn rocket() -> _ {
     dotenvy::dotenv().unwrap();
+    lazy_static::initialize(&util::jwt::KEYS);
+    lazy_static::initialize(&util::timezone::DEFAULT_TIMEZONE);
+    lazy_static::initialize(&util::cipher::SESSION_KEY_CIPHER);
+    lazy_static::initialize(&identity::password::DUMMY_HASH);
 
-    rocket::build()
+    let figment = rocket::Config::figment();
+    let limits = figment.extract_inner::<Limits>("limits").unwrap_or_default();
+    let figment = figment.merge(("limits", util::avatar::upload_limits(limits)));
+
+    let rocket = rocket::custom(figment)
         .attach(BpRecordConn::fairing())
+        .attach(job::archive::fairing())
+        .attach(job::erasure::fairing())
+        .attach(job::session_key::fairing())
+        .attach(job::search_index::fairing())
+        .attach(job::quota_plan::fairing())
+        .attach(job::export::fairing())
+        .attach(util::jwt::fairing())
+        .attach(util::timezone::fairing())
+        .manage(storage::from_env())
+        .manage(storage::exports_from_env())
+        .manage(sms::from_env())
+        .manage(wechat::WechatClient::from_env())
+        .register(
+            "/",
+            catchers![error::auth::unauthorized, error::auth::forbidden],
+        )
         .mount("/api", api::routes())
         .mount("/api/user", api::user::routes())
         .mount("/api/member", api::member::routes())
         .mount("/api/record", api::record::routes())
+        .mount("/api/fhir", api::fhir::routes())
+        .mount("/api/search", api::search::routes())
+        .mount("/api/invitation", api::invitation::routes())
+        .mount("/api/token", api::token::routes())
+        .mount("/api/admin", api::admin::routes())
+        .mount("/.well-known", api::well_known::routes())
+        .mount("/uploads", FileServer::from(storage::STORAGE_DIR.as_str()));
+    if *wechat::WECHAT_MOCK {
+        rocket.mount("/wechat-mock", wechat::mock::routes())
+    } else {
+        rocket
+    }
 }
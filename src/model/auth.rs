@@ -5,14 +5,16 @@ pub struct AuthBody {
     access_token: String,
     token_type: String,
     expires_in: i64,
+    refresh_token: String,
 }
 
 impl AuthBody {
-    pub fn new(access_token: String, expires_in: i64) -> Self {
+    pub fn new(access_token: String, expires_in: i64, refresh_token: String) -> Self {
         Self {
             access_token,
             token_type: String::from("Bearer"),
             expires_in,
+            refresh_token,
         }
     }
 }
@@ -21,4 +23,6 @@ impl AuthBody {
 pub struct WxUser {
     pub openid: String,
     pub session_key: String,
+    #[serde(default)]
+    pub unionid: Option<String>,
 }
@@ -0,0 +1,299 @@
+use crate::db::member::Members;
+use crate::db::record::Records;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct BpTarget {
+    pub systolic: i32,
+    pub diastolic: i32,
+    pub reason: String,
+}
+
+impl BpTarget {
+    pub fn for_member(member: &Members) -> Self {
+        let (systolic, diastolic, reason) = if member.diabetes || member.ckd {
+            (130, 80, "合并糖尿病或慢性肾病")
+        } else if member.age().is_some_and(|age| age >= 80) {
+            (150, 90, "80岁及以上老年人")
+        } else {
+            (140, 90, "一般高血压患者")
+        };
+        BpTarget {
+            systolic,
+            diastolic,
+            reason: String::from(reason),
+        }
+    }
+
+    pub fn reached(&self, systolic: i32, diastolic: i32) -> bool {
+        systolic < self.systolic && diastolic < self.diastolic
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BpCategory {
+    Normal,
+    HighNormal,
+    Grade1,
+    Grade2,
+    Grade3,
+    IsolatedSystolic,
+}
+
+impl BpCategory {
+    pub fn classify(member: &Members, systolic: i32, diastolic: i32) -> Option<Self> {
+        if member.age().is_some_and(|age| age < 18) {
+            return None;
+        }
+        let category = if systolic >= 180 || diastolic >= 110 {
+            BpCategory::Grade3
+        } else if systolic >= 160 || diastolic >= 100 {
+            BpCategory::Grade2
+        } else if systolic >= 140 && diastolic < 90 {
+            BpCategory::IsolatedSystolic
+        } else if systolic >= 140 || diastolic >= 90 {
+            BpCategory::Grade1
+        } else if systolic >= 120 || diastolic >= 80 {
+            BpCategory::HighNormal
+        } else {
+            BpCategory::Normal
+        };
+        Some(category)
+    }
+}
+
+/// Cardiovascular risk level from the blood pressure grade and the member's
+/// risk factors, after the 2018 Chinese hypertension guideline.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BpRisk {
+    Low,
+    Moderate,
+    High,
+    VeryHigh,
+}
+
+impl BpRisk {
+    /// Counts age (men over 55, women over 65), current smoking and obesity
+    /// (BMI of 28 or more); diabetes or CKD puts the member in the top band.
+    pub fn risk_factors(member: &Members) -> usize {
+        let elderly = match (member.sex.as_deref(), member.age()) {
+            (Some("male"), Some(age)) => age > 55,
+            (Some("female"), Some(age)) => age > 65,
+            _ => false,
+        };
+        let smoking = member.smoking.as_deref() == Some("current");
+        let obese = member.bmi().is_some_and(|bmi| bmi >= 28.0);
+        [elderly, smoking, obese]
+            .into_iter()
+            .filter(|&factor| factor)
+            .count()
+    }
+
+    pub fn assess(member: &Members, category: &BpCategory) -> Option<Self> {
+        // 0: no risk factor, 1: one or two, 2: three or more, 3: diabetes or CKD
+        let band = if member.diabetes || member.ckd {
+            3
+        } else {
+            match BpRisk::risk_factors(member) {
+                0 => 0,
+                1 | 2 => 1,
+                _ => 2,
+            }
+        };
+        let risk = match (category, band) {
+            (BpCategory::Normal, _) => return None,
+            (BpCategory::HighNormal, 0 | 1) => BpRisk::Low,
+            (BpCategory::HighNormal, 2) => BpRisk::Moderate,
+            (BpCategory::HighNormal, _) => BpRisk::High,
+            (BpCategory::Grade1 | BpCategory::IsolatedSystolic, 0) => BpRisk::Low,
+            (BpCategory::Grade1 | BpCategory::IsolatedSystolic, 1) => BpRisk::Moderate,
+            (BpCategory::Grade1 | BpCategory::IsolatedSystolic, _) => BpRisk::High,
+            (BpCategory::Grade2, 0 | 1) => BpRisk::Moderate,
+            (BpCategory::Grade2, 2) => BpRisk::High,
+            (BpCategory::Grade2, _) => BpRisk::VeryHigh,
+            (BpCategory::Grade3, 0 | 1) => BpRisk::High,
+            (BpCategory::Grade3, _) => BpRisk::VeryHigh,
+        };
+        Some(risk)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BpReading {
+    pub record: Records,
+    pub category: Option<BpCategory>,
+    pub risk: Option<BpRisk>,
+    pub at_target: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BpAssessment {
+    pub target: BpTarget,
+    pub latest: Option<BpReading>,
+}
+
+impl BpAssessment {
+    pub fn new(member: &Members, latest: Option<Records>) -> Self {
+        let target = BpTarget::for_member(member);
+        let latest = latest.map(|record| {
+            let category = BpCategory::classify(member, record.systolic, record.diastolic);
+            BpReading {
+                risk: category
+                    .as_ref()
+                    .and_then(|category| BpRisk::assess(member, category)),
+                category,
+                at_target: target.reached(record.systolic, record.diastolic),
+                record,
+            }
+        });
+        BpAssessment { target, latest }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Months, Utc};
+    use uuid::Uuid;
+
+    fn member(age: Option<u32>, sex: &str) -> Members {
+        let now = Utc::now().naive_utc();
+        Members {
+            id: Uuid::new_v4(),
+            name: String::from("张三"),
+            memo: None,
+            created_at: now,
+            updated_at: now,
+            birth_date: age.map(|age| {
+                now.date()
+                    .checked_sub_months(Months::new(age * 12 + 1))
+                    .unwrap()
+            }),
+            sex: Some(String::from(sex)),
+            height: Some(170),
+            weight: Some(65.0),
+            diabetes: false,
+            ckd: false,
+            smoking: None,
+            avatar_key: None,
+            avatar_url: None,
+        }
+    }
+
+    fn target(member: &Members) -> (i32, i32) {
+        let target = BpTarget::for_member(member);
+        (target.systolic, target.diastolic)
+    }
+
+    #[test]
+    fn target_is_stricter_with_diabetes_or_ckd() {
+        let mut diabetic = member(Some(60), "male");
+        diabetic.diabetes = true;
+        assert_eq!(target(&diabetic), (130, 80));
+        let mut ckd = member(Some(85), "female");
+        ckd.ckd = true;
+        assert_eq!(target(&ckd), (130, 80));
+    }
+
+    #[test]
+    fn target_is_relaxed_from_age_80() {
+        assert_eq!(target(&member(Some(80), "male")), (150, 90));
+        assert_eq!(target(&member(Some(79), "male")), (140, 90));
+        assert_eq!(target(&member(None, "male")), (140, 90));
+    }
+
+    #[test]
+    fn target_is_reached_below_both_values() {
+        let target = BpTarget::for_member(&member(Some(60), "male"));
+        assert!(target.reached(139, 89));
+        assert!(!target.reached(140, 89));
+        assert!(!target.reached(139, 90));
+    }
+
+    #[test]
+    fn category_boundaries() {
+        let member = member(Some(60), "male");
+        let cases = [
+            (119, 79, "normal"),
+            (120, 79, "high_normal"),
+            (119, 80, "high_normal"),
+            (139, 89, "high_normal"),
+            (140, 89, "isolated_systolic"),
+            (139, 90, "grade1"),
+            (140, 90, "grade1"),
+            (159, 99, "grade1"),
+            (160, 99, "grade2"),
+            (159, 100, "grade2"),
+            (179, 109, "grade2"),
+            (180, 85, "grade3"),
+            (179, 110, "grade3"),
+        ];
+        for (systolic, diastolic, expected) in cases {
+            let category = BpCategory::classify(&member, systolic, diastolic).unwrap();
+            assert_eq!(
+                serde_json::to_value(&category).unwrap(),
+                expected,
+                "{}/{}",
+                systolic,
+                diastolic
+            );
+        }
+    }
+
+    #[test]
+    fn category_is_not_given_for_minors() {
+        assert!(BpCategory::classify(&member(Some(17), "male"), 150, 95).is_none());
+        assert!(BpCategory::classify(&member(Some(18), "male"), 150, 95).is_some());
+    }
+
+    #[test]
+    fn risk_factors_count_age_smoking_and_obesity() {
+        assert_eq!(BpRisk::risk_factors(&member(Some(55), "male")), 0);
+        assert_eq!(BpRisk::risk_factors(&member(Some(56), "male")), 1);
+        assert_eq!(BpRisk::risk_factors(&member(Some(65), "female")), 0);
+        assert_eq!(BpRisk::risk_factors(&member(Some(66), "female")), 1);
+        let mut member = member(Some(70), "male");
+        member.smoking = Some(String::from("former"));
+        member.weight = Some(80.0);
+        assert_eq!(BpRisk::risk_factors(&member), 1);
+        member.smoking = Some(String::from("current"));
+        member.weight = Some(81.0);
+        assert_eq!(BpRisk::risk_factors(&member), 3);
+    }
+
+    #[test]
+    fn risk_strata() {
+        let none = member(Some(40), "male");
+        let mut one = member(Some(40), "male");
+        one.smoking = Some(String::from("current"));
+        let mut three = member(Some(60), "male");
+        three.smoking = Some(String::from("current"));
+        three.weight = Some(90.0);
+        let mut diabetic = member(Some(40), "male");
+        diabetic.diabetes = true;
+
+        assert_eq!(BpRisk::assess(&none, &BpCategory::Normal), None);
+        assert_eq!(
+            BpRisk::assess(&none, &BpCategory::Grade1),
+            Some(BpRisk::Low)
+        );
+        assert_eq!(
+            BpRisk::assess(&one, &BpCategory::Grade1),
+            Some(BpRisk::Moderate)
+        );
+        assert_eq!(
+            BpRisk::assess(&three, &BpCategory::Grade1),
+            Some(BpRisk::High)
+        );
+        assert_eq!(
+            BpRisk::assess(&diabetic, &BpCategory::Grade2),
+            Some(BpRisk::VeryHigh)
+        );
+        assert_eq!(
+            BpRisk::assess(&diabetic, &BpCategory::HighNormal),
+            Some(BpRisk::High)
+        );
+    }
+}
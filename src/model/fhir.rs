@@ -0,0 +1,840 @@
+use crate::db::member::Members;
+use crate::db::record::{NewRecord, Records};
+use crate::error::api::ApiError;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use uuid::Uuid;
+
+pub const LOINC_SYSTEM: &str = "http://loinc.org";
+pub const UCUM_SYSTEM: &str = "http://unitsofmeasure.org";
+pub const OBSERVATION_CATEGORY_SYSTEM: &str =
+    "http://terminology.hl7.org/CodeSystem/observation-category";
+
+pub const LOINC_BP_PANEL: &str = "85354-9";
+pub const LOINC_SYSTOLIC: &str = "8480-6";
+pub const LOINC_DIASTOLIC: &str = "8462-4";
+pub const LOINC_HEART_RATE: &str = "8867-4";
+
+/// Readings outside these bounds are treated as entry errors and skipped.
+const SYSTOLIC_RANGE: RangeInclusive<f64> = 50.0..=300.0;
+const DIASTOLIC_RANGE: RangeInclusive<f64> = 20.0..=200.0;
+const HEART_RATE_RANGE: RangeInclusive<f64> = 20.0..=300.0;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bundle {
+    pub resource_type: String,
+    #[serde(rename = "type")]
+    pub bundle_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<usize>,
+    #[serde(default)]
+    pub entry: Vec<BundleEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleEntry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub full_url: Option<String>,
+    pub resource: Resource,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search: Option<BundleSearch>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleSearch {
+    pub mode: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "resourceType")]
+pub enum Resource {
+    Patient(Patient),
+    Observation(Box<Observation>),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Patient {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub name: Vec<HumanName>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gender: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub birth_date: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HumanName {
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Observation {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub category: Vec<CodeableConcept>,
+    pub code: CodeableConcept,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<Reference>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effective_date_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_quantity: Option<Quantity>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub component: Vec<ObservationComponent>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub note: Vec<Annotation>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObservationComponent {
+    pub code: CodeableConcept,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_quantity: Option<Quantity>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CodeableConcept {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub coding: Vec<Coding>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Coding {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Quantity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Annotation {
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Reference {
+    pub reference: String,
+}
+
+impl CodeableConcept {
+    pub fn loinc(code: &str, display: &str) -> Self {
+        CodeableConcept {
+            coding: vec![Coding {
+                system: Some(String::from(LOINC_SYSTEM)),
+                code: Some(String::from(code)),
+                display: Some(String::from(display)),
+            }],
+            text: Some(String::from(display)),
+        }
+    }
+
+    pub fn has_loinc(&self, code: &str) -> bool {
+        self.coding.iter().any(|coding| {
+            coding.system.as_deref() == Some(LOINC_SYSTEM) && coding.code.as_deref() == Some(code)
+        })
+    }
+}
+
+impl Quantity {
+    pub fn mm_hg(value: i32) -> Self {
+        Quantity {
+            value: Some(value as f64),
+            unit: Some(String::from("mmHg")),
+            system: Some(String::from(UCUM_SYSTEM)),
+            code: Some(String::from("mm[Hg]")),
+        }
+    }
+
+    pub fn per_minute(value: i32) -> Self {
+        Quantity {
+            value: Some(value as f64),
+            unit: Some(String::from("beats/minute")),
+            system: Some(String::from(UCUM_SYSTEM)),
+            code: Some(String::from("/min")),
+        }
+    }
+}
+
+impl From<&Members> for Patient {
+    fn from(member: &Members) -> Self {
+        Patient {
+            id: member.id.to_string(),
+            name: vec![HumanName {
+                text: member.name.clone(),
+            }],
+            gender: member.sex.clone(),
+            birth_date: member
+                .birth_date
+                .map(|birth_date| birth_date.format("%Y-%m-%d").to_string()),
+        }
+    }
+}
+
+impl From<&Records> for Observation {
+    fn from(record: &Records) -> Self {
+        Observation {
+            id: Some(record.id.to_string()),
+            status: String::from("final"),
+            category: vec![CodeableConcept {
+                coding: vec![Coding {
+                    system: Some(String::from(OBSERVATION_CATEGORY_SYSTEM)),
+                    code: Some(String::from("vital-signs")),
+                    display: Some(String::from("Vital Signs")),
+                }],
+                text: None,
+            }],
+            code: CodeableConcept::loinc(LOINC_BP_PANEL, "Blood pressure panel"),
+            subject: Some(patient_reference(record.member_id)),
+            effective_date_time: Some(fhir_date_time(&record.record_at)),
+            value_quantity: None,
+            component: vec![
+                ObservationComponent {
+                    code: CodeableConcept::loinc(LOINC_SYSTOLIC, "Systolic blood pressure"),
+                    value_quantity: Some(Quantity::mm_hg(record.systolic)),
+                },
+                ObservationComponent {
+                    code: CodeableConcept::loinc(LOINC_DIASTOLIC, "Diastolic blood pressure"),
+                    value_quantity: Some(Quantity::mm_hg(record.diastolic)),
+                },
+                ObservationComponent {
+                    code: CodeableConcept::loinc(LOINC_HEART_RATE, "Heart rate"),
+                    value_quantity: Some(Quantity::per_minute(record.bmp)),
+                },
+            ],
+            note: record
+                .note
+                .iter()
+                .map(|text| Annotation { text: text.clone() })
+                .collect(),
+        }
+    }
+}
+
+impl Bundle {
+    pub fn observations(member: &Members, records: &[Records]) -> Self {
+        let mut entry = Vec::with_capacity(records.len() + 1);
+        entry.push(BundleEntry {
+            full_url: Some(format!("urn:uuid:{}", member.id)),
+            resource: Resource::Patient(Patient::from(member)),
+            search: Some(BundleSearch {
+                mode: String::from("include"),
+            }),
+        });
+        for record in records {
+            entry.push(BundleEntry {
+                full_url: Some(format!("urn:uuid:{}", record.id)),
+                resource: Resource::Observation(Box::new(Observation::from(record))),
+                search: Some(BundleSearch {
+                    mode: String::from("match"),
+                }),
+            });
+        }
+        Bundle {
+            resource_type: String::from("Bundle"),
+            bundle_type: String::from("searchset"),
+            total: Some(records.len()),
+            entry,
+        }
+    }
+}
+
+/// Points at the Patient entry's `fullUrl`, so the reference resolves
+/// within the Bundle.
+pub fn patient_reference(member_id: Uuid) -> Reference {
+    Reference {
+        reference: format!("urn:uuid:{}", member_id),
+    }
+}
+
+pub fn fhir_date_time(date: &NaiveDateTime) -> String {
+    date.and_utc().format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+/// Parses a FHIR `dateTime` of at least day precision. A date without a time is
+/// taken as midnight UTC; a year or year-month cannot place a reading and is
+/// rejected with a reason saying so.
+pub fn parse_date_time(value: &str) -> Result<NaiveDateTime, String> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Ok(date.naive_utc());
+    }
+    let minutes = match value.strip_suffix('Z') {
+        Some(local) => format!("{}+00:00", local),
+        None => value.to_owned(),
+    };
+    if let Ok(date) = DateTime::parse_from_str(&minutes, "%Y-%m-%dT%H:%M%:z") {
+        return Ok(date.naive_utc());
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_time(Default::default()));
+    }
+    let partial = [format!("{}-01", value), format!("{}-01-01", value)];
+    if partial
+        .iter()
+        .any(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok())
+    {
+        return Err(format!(
+            "effectiveDateTime {} 精度不足，至少需要到日",
+            value
+        ));
+    }
+    Err(format!("effectiveDateTime {} 不是有效的时间", value))
+}
+
+#[derive(Debug, Serialize)]
+pub struct SkippedResource {
+    pub index: usize,
+    pub resource_type: Option<String>,
+    pub id: Option<String>,
+    pub reason: String,
+}
+
+impl SkippedResource {
+    fn new(index: usize, resource: &Value, reason: impl Into<String>) -> Self {
+        SkippedResource {
+            index,
+            resource_type: resource["resourceType"].as_str().map(String::from),
+            id: resource["id"].as_str().map(String::from),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// The entry an extracted reading came from, so it can still be reported as
+/// skipped when the database already holds the reading.
+pub struct SourceEntry {
+    index: usize,
+    resource_type: Option<String>,
+    id: Option<String>,
+}
+
+impl SourceEntry {
+    fn new(index: usize, resource: &Value) -> Self {
+        SourceEntry {
+            index,
+            resource_type: resource["resourceType"].as_str().map(String::from),
+            id: resource["id"].as_str().map(String::from),
+        }
+    }
+
+    pub fn skip(self, reason: impl Into<String>) -> SkippedResource {
+        SkippedResource {
+            index: self.index,
+            resource_type: self.resource_type,
+            id: self.id,
+            reason: reason.into(),
+        }
+    }
+}
+
+pub type ExtractedRecord = (SourceEntry, NewRecord);
+
+struct PanelReading {
+    index: usize,
+    resource: Value,
+    systolic: i32,
+    diastolic: i32,
+    bmp: Option<i32>,
+    note: Option<String>,
+    record_at: NaiveDateTime,
+}
+
+pub fn extract_records(
+    payload: Value,
+) -> Result<(Vec<ExtractedRecord>, Vec<SkippedResource>), ApiError> {
+    let resources = match payload["resourceType"].as_str() {
+        Some("Bundle") => match payload {
+            Value::Object(mut bundle) => match bundle.remove("entry") {
+                Some(Value::Array(entries)) => entries
+                    .into_iter()
+                    .map(|mut entry| entry["resource"].take())
+                    .collect(),
+                _ => Vec::new(),
+            },
+            _ => Vec::new(),
+        },
+        Some("Observation") => vec![payload],
+        _ => {
+            return Err(ApiError::BadRequest(String::from(
+                "仅支持 Bundle 或 Observation 资源",
+            )));
+        }
+    };
+
+    let mut skipped = Vec::new();
+    let mut panels = Vec::new();
+    let mut heart_rates: HashMap<NaiveDateTime, (usize, Value, i32)> = HashMap::new();
+
+    for (index, resource) in resources.into_iter().enumerate() {
+        if resource["resourceType"].as_str() != Some("Observation") {
+            skipped.push(SkippedResource::new(index, &resource, "不支持的资源类型"));
+            continue;
+        }
+        let observation = match serde_json::from_value::<Observation>(resource.clone()) {
+            Ok(observation) => observation,
+            Err(err) => {
+                skipped.push(SkippedResource::new(
+                    index,
+                    &resource,
+                    format!("Observation 格式错误: {}", err),
+                ));
+                continue;
+            }
+        };
+        if matches!(
+            observation.status.as_str(),
+            "entered-in-error" | "cancelled" | "registered"
+        ) {
+            skipped.push(SkippedResource::new(
+                index,
+                &resource,
+                format!("状态为 {} 的观测不导入", observation.status),
+            ));
+            continue;
+        }
+        let record_at = match observation
+            .effective_date_time
+            .as_deref()
+            .map(parse_date_time)
+        {
+            Some(Ok(record_at)) => record_at,
+            Some(Err(reason)) => {
+                skipped.push(SkippedResource::new(index, &resource, reason));
+                continue;
+            }
+            None => {
+                skipped.push(SkippedResource::new(
+                    index,
+                    &resource,
+                    "缺少 effectiveDateTime",
+                ));
+                continue;
+            }
+        };
+
+        let systolic = component_value(&observation, LOINC_SYSTOLIC);
+        let diastolic = component_value(&observation, LOINC_DIASTOLIC);
+        if observation.code.has_loinc(LOINC_BP_PANEL) || systolic.is_some() || diastolic.is_some() {
+            match (systolic, diastolic) {
+                (Some(Ok(systolic)), Some(Ok(diastolic))) => {
+                    let (systolic, diastolic, bmp) =
+                        match panel_values(&observation, systolic, diastolic) {
+                            Ok(reading) => reading,
+                            Err(reason) => {
+                                skipped.push(SkippedResource::new(index, &resource, reason));
+                                continue;
+                            }
+                        };
+                    panels.push(PanelReading {
+                        index,
+                        resource,
+                        systolic,
+                        diastolic,
+                        bmp,
+                        note: observation_note(&observation),
+                        record_at,
+                    });
+                }
+                (Some(Err(reason)), _) | (_, Some(Err(reason))) => {
+                    skipped.push(SkippedResource::new(index, &resource, reason));
+                }
+                _ => {
+                    skipped.push(SkippedResource::new(index, &resource, "缺少收缩压或舒张压"));
+                }
+            }
+        } else if observation.code.has_loinc(LOINC_HEART_RATE) {
+            let bmp = observation
+                .value_quantity
+                .as_ref()
+                .map(|quantity| plausible("心率", heart_rate_value(quantity)?, HEART_RATE_RANGE));
+            match bmp {
+                Some(Ok(bmp)) => {
+                    if let Some((index, resource, _)) =
+                        heart_rates.insert(record_at, (index, resource, bmp))
+                    {
+                        skipped.push(SkippedResource::new(
+                            index,
+                            &resource,
+                            "同一时间存在多个心率",
+                        ));
+                    }
+                }
+                Some(Err(reason)) => skipped.push(SkippedResource::new(index, &resource, reason)),
+                None => skipped.push(SkippedResource::new(index, &resource, "缺少心率数值")),
+            }
+        } else {
+            skipped.push(SkippedResource::new(index, &resource, "不是血压或心率观测"));
+        }
+    }
+
+    let mut records = Vec::with_capacity(panels.len());
+    for panel in panels {
+        let bmp = match panel.bmp {
+            Some(bmp) => {
+                heart_rates.remove(&panel.record_at);
+                Some(bmp)
+            }
+            None => heart_rates.remove(&panel.record_at).map(|(_, _, bmp)| bmp),
+        };
+        match bmp {
+            Some(bmp) => records.push((
+                SourceEntry::new(panel.index, &panel.resource),
+                NewRecord {
+                    systolic: panel.systolic,
+                    diastolic: panel.diastolic,
+                    bmp,
+                    record_at: panel.record_at,
+                    note: panel.note,
+                },
+            )),
+            None => skipped.push(SkippedResource::new(
+                panel.index,
+                &panel.resource,
+                "缺少同一时间的心率",
+            )),
+        }
+    }
+    for (_, (index, resource, _)) in heart_rates {
+        skipped.push(SkippedResource::new(index, &resource, "缺少同一时间的血压"));
+    }
+    skipped.sort_by_key(|skipped| skipped.index);
+
+    Ok((records, skipped))
+}
+
+fn observation_note(observation: &Observation) -> Option<String> {
+    let texts = observation
+        .note
+        .iter()
+        .map(|annotation| annotation.text.trim())
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>();
+    if texts.is_empty() {
+        None
+    } else {
+        Some(texts.join("\n"))
+    }
+}
+
+/// Range-checks a panel's values; a heart rate component that cannot be read
+/// is left to a separate heart rate Observation.
+fn panel_values(
+    observation: &Observation,
+    systolic: f64,
+    diastolic: f64,
+) -> Result<(i32, i32, Option<i32>), String> {
+    let systolic = plausible("收缩压", systolic, SYSTOLIC_RANGE)?;
+    let diastolic = plausible("舒张压", diastolic, DIASTOLIC_RANGE)?;
+    if systolic <= diastolic {
+        return Err(format!("收缩压 {} 不高于舒张压 {}", systolic, diastolic));
+    }
+    let bmp = match component_value(observation, LOINC_HEART_RATE) {
+        Some(Ok(bmp)) => Some(plausible("心率", bmp, HEART_RATE_RANGE)?),
+        _ => None,
+    };
+    Ok((systolic, diastolic, bmp))
+}
+
+fn plausible(name: &str, value: f64, range: RangeInclusive<f64>) -> Result<i32, String> {
+    if !range.contains(&value) {
+        return Err(format!(
+            "{} {} 超出合理范围 {}-{}",
+            name,
+            value,
+            range.start(),
+            range.end()
+        ));
+    }
+    Ok(value.round() as i32)
+}
+
+fn component_value(observation: &Observation, code: &str) -> Option<Result<f64, String>> {
+    observation
+        .component
+        .iter()
+        .find(|component| component.code.has_loinc(code))
+        .map(|component| match component.value_quantity.as_ref() {
+            Some(quantity) if code == LOINC_HEART_RATE => heart_rate_value(quantity),
+            Some(quantity) => pressure_value(quantity),
+            None => Err(format!("{} 缺少数值", code)),
+        })
+}
+
+fn pressure_value(quantity: &Quantity) -> Result<f64, String> {
+    let value = quantity.value.ok_or_else(|| String::from("血压缺少数值"))?;
+    match quantity.code.as_deref().or(quantity.unit.as_deref()) {
+        None | Some("mm[Hg]") | Some("mmHg") => Ok(value),
+        Some("kPa") => Ok(value * 7.50062),
+        Some(unit) => Err(format!("不支持的血压单位 {}", unit)),
+    }
+}
+
+fn heart_rate_value(quantity: &Quantity) -> Result<f64, String> {
+    let value = quantity.value.ok_or_else(|| String::from("心率缺少数值"))?;
+    match quantity.code.as_deref().or(quantity.unit.as_deref()) {
+        None | Some("/min") | Some("beats/minute") | Some("{beats}/min") => Ok(value),
+        Some(unit) => Err(format!("不支持的心率单位 {}", unit)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::record::{ArchivedRecords, merge_history};
+    use chrono::NaiveDate;
+    use std::env;
+    use std::fs;
+
+    fn member() -> Members {
+        let now = NaiveDate::from_ymd_opt(2026, 10, 19)
+            .unwrap()
+            .and_hms_opt(8, 0, 0)
+            .unwrap();
+        Members {
+            id: Uuid::new_v4(),
+            name: String::from("张三"),
+            memo: None,
+            created_at: now,
+            updated_at: now,
+            birth_date: NaiveDate::from_ymd_opt(1960, 5, 1),
+            sex: Some(String::from("male")),
+            height: Some(170),
+            weight: Some(65.5),
+            diabetes: false,
+            ckd: false,
+            smoking: None,
+            avatar_key: None,
+            avatar_url: None,
+        }
+    }
+
+    fn record(member_id: Uuid, hour: u32, note: Option<&str>) -> Records {
+        let record_at = NaiveDate::from_ymd_opt(2026, 10, 18)
+            .unwrap()
+            .and_hms_opt(hour, 30, 0)
+            .unwrap();
+        Records {
+            id: Uuid::new_v4(),
+            member_id,
+            systolic: 135,
+            diastolic: 85,
+            bmp: 72,
+            record_at,
+            created_at: record_at,
+            updated_at: record_at,
+            note: note.map(String::from),
+        }
+    }
+
+    /// Uses the schema excerpt in `tests/fixtures` unless `FHIR_SCHEMA` points
+    /// at the full R4 `fhir.schema.json`.
+    fn schema() -> Value {
+        let path = env::var("FHIR_SCHEMA").unwrap_or_else(|_| {
+            format!(
+                "{}/tests/fixtures/fhir.schema.json",
+                env!("CARGO_MANIFEST_DIR")
+            )
+        });
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn observation_bundle_validates_against_schema() {
+        let member = member();
+        let records = vec![
+            record(member.id, 7, Some("晨起")),
+            record(member.id, 21, None),
+        ];
+        let bundle = serde_json::to_value(Bundle::observations(&member, &records)).unwrap();
+        let validator = jsonschema::draft6::new(&schema()).unwrap();
+        let errors = validator
+            .iter_errors(&bundle)
+            .map(|err| format!("{} at {}", err, err.instance_path))
+            .collect::<Vec<_>>();
+        assert!(errors.is_empty(), "{:#?}", errors);
+    }
+
+    #[test]
+    fn observation_subject_resolves_within_bundle() {
+        let member = member();
+        let records = vec![record(member.id, 7, None)];
+        let bundle = serde_json::to_value(Bundle::observations(&member, &records)).unwrap();
+        let entries = bundle["entry"].as_array().unwrap();
+        let patient_url = entries[0]["fullUrl"].as_str().unwrap();
+        assert_eq!(entries[0]["resource"]["resourceType"], "Patient");
+        assert_eq!(
+            entries[1]["resource"]["subject"]["reference"].as_str(),
+            Some(patient_url)
+        );
+    }
+
+    #[test]
+    fn archived_record_is_exported() {
+        let member = member();
+        let live = record(member.id, 7, None);
+        let old = record(member.id, 6, Some("住院期间"));
+        let archived = ArchivedRecords {
+            id: old.id,
+            member_id: old.member_id,
+            systolic: old.systolic,
+            diastolic: old.diastolic,
+            bmp: old.bmp,
+            record_at: old.record_at - chrono::Months::new(13),
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+            archived_at: old.updated_at,
+            note: old.note,
+        };
+        let records = merge_history(vec![live], vec![archived]);
+        let bundle = serde_json::to_value(Bundle::observations(&member, &records)).unwrap();
+        assert_eq!(bundle["total"], 2);
+        let observation = &bundle["entry"][2]["resource"];
+        assert_eq!(observation["id"], old.id.to_string());
+        assert_eq!(observation["effectiveDateTime"], "2025-09-18T06:30:00Z");
+        assert_eq!(observation["note"][0]["text"], "住院期间");
+    }
+
+    fn panel(id: &str, systolic: f64, diastolic: f64) -> Value {
+        serde_json::json!({
+            "resourceType": "Observation",
+            "id": id,
+            "status": "final",
+            "code": { "coding": [{ "system": LOINC_SYSTEM, "code": LOINC_BP_PANEL }] },
+            "effectiveDateTime": "2026-10-18T07:30:00+08:00",
+            "component": [
+                {
+                    "code": { "coding": [{ "system": LOINC_SYSTEM, "code": LOINC_SYSTOLIC }] },
+                    "valueQuantity": { "value": systolic, "code": "mm[Hg]" }
+                },
+                {
+                    "code": { "coding": [{ "system": LOINC_SYSTEM, "code": LOINC_DIASTOLIC }] },
+                    "valueQuantity": { "value": diastolic, "code": "mm[Hg]" }
+                },
+                {
+                    "code": { "coding": [{ "system": LOINC_SYSTEM, "code": LOINC_HEART_RATE }] },
+                    "valueQuantity": { "value": 70, "code": "/min" }
+                }
+            ]
+        })
+    }
+
+    fn bundle(entries: Vec<Value>) -> Value {
+        serde_json::json!({
+            "resourceType": "Bundle",
+            "type": "collection",
+            "entry": entries
+                .into_iter()
+                .map(|resource| serde_json::json!({ "resource": resource }))
+                .collect::<Vec<_>>()
+        })
+    }
+
+    fn skip_reason(payload: Value) -> String {
+        let (records, skipped) = extract_records(payload).unwrap();
+        assert!(records.is_empty(), "{} records imported", records.len());
+        skipped[0].reason.clone()
+    }
+
+    fn at(mut panel: Value, effective_date_time: &str) -> Value {
+        panel["effectiveDateTime"] = Value::from(effective_date_time);
+        panel
+    }
+
+    #[test]
+    fn import_accepts_plausible_reading() {
+        let (records, skipped) = extract_records(panel("bp-1", 128.4, 82.0)).unwrap();
+        assert!(skipped.is_empty());
+        let record = &records[0].1;
+        assert_eq!(
+            (record.systolic, record.diastolic, record.bmp),
+            (128, 82, 70)
+        );
+    }
+
+    #[test]
+    fn import_skips_implausible_reading() {
+        let (records, skipped) = extract_records(bundle(vec![
+            panel("bp-1", 120.0, 80.0),
+            panel("bp-2", 100000.0, 80.0),
+        ]))
+        .unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].1.systolic, 120);
+        assert_eq!(
+            (skipped[0].index, skipped[0].id.as_deref()),
+            (1, Some("bp-2"))
+        );
+        assert!(
+            skipped[0].reason.contains("超出合理范围"),
+            "{}",
+            skipped[0].reason
+        );
+        assert!(skip_reason(panel("bp-3", -120.0, 80.0)).contains("收缩压"));
+        assert!(skip_reason(panel("bp-4", 120.0, 0.0)).contains("舒张压"));
+    }
+
+    #[test]
+    fn import_skips_systolic_not_above_diastolic() {
+        let reason = skip_reason(panel("bp-5", 80.0, 80.0));
+        assert!(reason.contains("不高于舒张压"), "{}", reason);
+    }
+
+    #[test]
+    fn import_accepts_date_and_minute_precision() {
+        let (records, skipped) = extract_records(bundle(vec![
+            at(panel("bp-1", 120.0, 80.0), "2026-10-18"),
+            at(panel("bp-2", 121.0, 80.0), "2026-10-18T07:30+08:00"),
+            at(panel("bp-3", 122.0, 80.0), "2026-10-18T07:30Z"),
+        ]))
+        .unwrap();
+        assert!(skipped.is_empty(), "{:?}", skipped);
+        let record_ats = records
+            .iter()
+            .map(|(_, record)| record.record_at.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            record_ats,
+            [
+                "2026-10-18 00:00:00",
+                "2026-10-17 23:30:00",
+                "2026-10-18 07:30:00"
+            ]
+        );
+    }
+
+    #[test]
+    fn import_skips_year_and_month_precision() {
+        for value in ["2026", "2026-10"] {
+            let reason = skip_reason(at(panel("bp-1", 120.0, 80.0), value));
+            assert!(reason.contains("精度不足"), "{}", reason);
+        }
+        let reason = skip_reason(at(panel("bp-1", 120.0, 80.0), "yesterday"));
+        assert!(reason.contains("不是有效的时间"), "{}", reason);
+    }
+}
@@ -1 +1,3 @@
-pub mod auth;
\ No newline at end of file
+pub mod auth;
+pub mod bp;
+pub mod fhir;
@@ -1,16 +1,126 @@
 // @generated automatically by Diesel CLI.
 
+pub mod sql_types {
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "tsquery", schema = "pg_catalog"))]
+    pub struct Tsquery;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "tsvector", schema = "pg_catalog"))]
+    pub struct Tsvector;
+}
+
+diesel::table! {
+    account_deletions (user_id) {
+        user_id -> Uuid,
+        code_hash -> Varchar,
+        code_expires_at -> Timestamptz,
+        erase_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    account_erasures (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        requested_at -> Timestamptz,
+        member_num -> Int4,
+        record_num -> Int4,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    admin_audit_logs (id) {
+        id -> Uuid,
+        admin_id -> Uuid,
+        action -> Varchar,
+        target_user_id -> Nullable<Uuid>,
+        detail -> Jsonb,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    data_exports (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        token_hash -> Varchar,
+        status -> Varchar,
+        storage_key -> Nullable<Varchar>,
+        expires_at -> Timestamptz,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    login_events (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        provider -> Varchar,
+        ip -> Nullable<Varchar>,
+        user_agent -> Nullable<Varchar>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    member_invitations (id) {
+        id -> Uuid,
+        member_id -> Uuid,
+        inviter_id -> Uuid,
+        code_hash -> Varchar,
+        role -> Varchar,
+        expires_at -> Timestamptz,
+        accepted_by -> Nullable<Uuid>,
+        accepted_at -> Nullable<Timestamptz>,
+        revoked_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Tsvector;
+
     members (id) {
         id -> Uuid,
         name -> Varchar,
         memo -> Nullable<Varchar>,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        search_vector -> Tsvector,
+        birth_date -> Nullable<Date>,
+        sex -> Nullable<Varchar>,
+        height -> Nullable<Int4>,
+        weight -> Nullable<Float4>,
+        diabetes -> Bool,
+        ckd -> Bool,
+        smoking -> Nullable<Varchar>,
+        avatar_key -> Nullable<Varchar>,
+        avatar_url -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    quota_plans (id) {
+        id -> Uuid,
+        name -> Varchar,
+        member_limit -> Int4,
+        is_default -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Tsvector;
+
     records (id) {
         id -> Uuid,
         member_id -> Uuid,
@@ -20,6 +130,75 @@ diesel::table! {
         record_at -> Timestamptz,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        note -> Nullable<Varchar>,
+        search_vector -> Tsvector,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Tsvector;
+
+    records_archive (id) {
+        id -> Uuid,
+        member_id -> Uuid,
+        systolic -> Int4,
+        diastolic -> Int4,
+        bmp -> Int4,
+        record_at -> Timestamptz,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        archived_at -> Timestamptz,
+        note -> Nullable<Varchar>,
+        search_vector -> Tsvector,
+    }
+}
+
+diesel::table! {
+    refresh_tokens (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        family_id -> Uuid,
+        token_hash -> Varchar,
+        expires_at -> Timestamptz,
+        replaced_by -> Nullable<Uuid>,
+        revoked_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    revoked_tokens (jti) {
+        jti -> Uuid,
+        user_id -> Uuid,
+        expires_at -> Timestamptz,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    sms_codes (id) {
+        id -> Uuid,
+        phone -> Varchar,
+        code_hash -> Varchar,
+        expires_at -> Timestamptz,
+        attempts -> Int4,
+        consumed_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    user_identities (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        provider -> Varchar,
+        subject -> Varchar,
+        credential -> Nullable<Varchar>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
@@ -29,21 +208,73 @@ diesel::table! {
         member_id -> Uuid,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        role -> Varchar,
+        sort_order -> Nullable<Int4>,
+        pinned -> Bool,
+    }
+}
+
+diesel::table! {
+    user_profiles (user_id) {
+        user_id -> Uuid,
+        timezone -> Varchar,
+        language -> Varchar,
+        bp_unit -> Varchar,
+        date_format -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    user_quotas (user_id) {
+        user_id -> Uuid,
+        plan_id -> Nullable<Uuid>,
+        member_limit -> Nullable<Int4>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
 diesel::table! {
     users (id) {
         id -> Uuid,
-        openid -> Varchar,
-        session_key -> Varchar,
+        session_key -> Nullable<Varchar>,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        token_generation -> Int4,
+        nickname -> Nullable<Varchar>,
+        avatar_url -> Nullable<Varchar>,
+        phone -> Nullable<Varchar>,
+        unionid -> Nullable<Varchar>,
+        role -> Varchar,
+        disabled_at -> Nullable<Timestamptz>,
     }
 }
 
+diesel::joinable!(user_identities -> users (user_id));
 diesel::joinable!(user_member -> users (user_id));
 diesel::joinable!(user_member -> members (member_id));
+diesel::joinable!(user_quotas -> quota_plans (plan_id));
 diesel::joinable!(records -> members (member_id));
 
-diesel::allow_tables_to_appear_in_same_query!(members, records, user_member, users,);
+diesel::allow_tables_to_appear_in_same_query!(
+    account_deletions,
+    account_erasures,
+    admin_audit_logs,
+    data_exports,
+    login_events,
+    member_invitations,
+    members,
+    quota_plans,
+    records,
+    records_archive,
+    refresh_tokens,
+    revoked_tokens,
+    sms_codes,
+    user_identities,
+    user_member,
+    user_profiles,
+    user_quotas,
+    users,
+);
@@ -0,0 +1,21 @@
+use crate::sms::log::LogSmsSender;
+use lazy_static::lazy_static;
+use std::env;
+
+pub mod log;
+
+lazy_static! {
+    pub static ref SMS_SENDER: String = env::var("SMS_SENDER").unwrap_or_else(|_| "log".to_owned());
+}
+
+#[rocket::async_trait]
+pub trait SmsSender: Send + Sync {
+    async fn send_code(&self, phone: &str, code: &str) -> anyhow::Result<()>;
+}
+
+pub fn from_env() -> Box<dyn SmsSender> {
+    match SMS_SENDER.as_str() {
+        "log" => Box::new(LogSmsSender),
+        sender => panic!("unsupported SMS_SENDER: {}", sender),
+    }
+}
@@ -0,0 +1,57 @@
+use crate::storage::Storage;
+use anyhow::bail;
+use rocket::tokio::fs;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+
+pub struct LocalStorage {
+    root: PathBuf,
+    base_url: String,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>, base_url: &str) -> Self {
+        Self {
+            root: root.into(),
+            base_url: base_url.trim_end_matches('/').to_owned(),
+        }
+    }
+
+    fn path(&self, key: &str) -> anyhow::Result<PathBuf> {
+        let key = Path::new(key);
+        if !key
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            bail!("invalid storage key: {}", key.display());
+        }
+        Ok(self.root.join(key))
+    }
+}
+
+#[rocket::async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> anyhow::Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(path, bytes).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Vec<u8>> {
+        Ok(fs::read(self.path(key)?).await?)
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        match fs::remove_file(self.path(key)?).await {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}/{}", self.base_url, key)
+    }
+}
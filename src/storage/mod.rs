@@ -0,0 +1,40 @@
+use crate::storage::local::LocalStorage;
+use lazy_static::lazy_static;
+use std::env;
+use std::sync::Arc;
+
+pub mod local;
+
+lazy_static! {
+    pub static ref STORAGE_DIR: String =
+        env::var("STORAGE_DIR").unwrap_or_else(|_| "uploads".to_owned());
+    pub static ref STORAGE_URL: String =
+        env::var("STORAGE_URL").unwrap_or_else(|_| "/uploads".to_owned());
+    pub static ref EXPORT_DIR: String =
+        env::var("EXPORT_DIR").unwrap_or_else(|_| "exports".to_owned());
+}
+
+#[rocket::async_trait]
+pub trait Storage: Send + Sync {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> anyhow::Result<()>;
+    async fn get(&self, key: &str) -> anyhow::Result<Vec<u8>>;
+    async fn delete(&self, key: &str) -> anyhow::Result<()>;
+    fn url(&self, key: &str) -> String;
+}
+
+pub fn from_env() -> Box<dyn Storage> {
+    std::fs::create_dir_all(STORAGE_DIR.as_str()).unwrap();
+    Box::new(LocalStorage::new(
+        STORAGE_DIR.as_str(),
+        STORAGE_URL.as_str(),
+    ))
+}
+
+/// Personal data exports, kept apart from `STORAGE_DIR` since that one is
+/// served publicly.
+pub struct ExportStorage(pub Arc<dyn Storage>);
+
+pub fn exports_from_env() -> ExportStorage {
+    std::fs::create_dir_all(EXPORT_DIR.as_str()).unwrap();
+    ExportStorage(Arc::new(LocalStorage::new(EXPORT_DIR.as_str(), "")))
+}
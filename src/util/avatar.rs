@@ -0,0 +1,69 @@
+use crate::error::api::ApiError;
+use image::error::ImageError;
+use image::imageops::FilterType;
+use image::{ImageFormat, ImageReader};
+use lazy_static::lazy_static;
+use rocket::data::{ByteUnit, Limits, ToByteUnit};
+use std::env;
+use std::io::Cursor;
+
+/// Bounds decoding, so a small but highly compressed image cannot expand
+/// into hundreds of megabytes.
+const AVATAR_MAX_DIMENSION: u32 = 4096;
+const AVATAR_MAX_ALLOC: u64 = 64 * 1024 * 1024;
+
+lazy_static! {
+    pub static ref AVATAR_MAX_SIZE: u64 = {
+        env::var("AVATAR_MAX_SIZE")
+            .unwrap_or_else(|_| "2097152".to_owned())
+            .parse::<u64>()
+            .unwrap()
+    };
+    pub static ref AVATAR_SIZE: u32 = {
+        env::var("AVATAR_SIZE")
+            .unwrap_or_else(|_| "256".to_owned())
+            .parse::<u32>()
+            .unwrap()
+    };
+}
+
+/// Raises Rocket's `file` and `data-form` limits to `AVATAR_MAX_SIZE`, so an
+/// allowed avatar is not cut off by the 1 MiB default before it reaches the
+/// handler. Larger configured limits are kept.
+pub fn upload_limits(limits: Limits) -> Limits {
+    let file = ByteUnit::from(*AVATAR_MAX_SIZE);
+    // Room for the multipart boundaries and the other fields of the form.
+    let form = file + 64.kibibytes();
+    let file = limits.get("file").map_or(file, |limit| limit.max(file));
+    let form = limits
+        .get("data-form")
+        .map_or(form, |limit| limit.max(form));
+    limits.limit("file", file).limit("data-form", form)
+}
+
+pub fn thumbnail(bytes: &[u8]) -> Result<Vec<u8>, ApiError> {
+    let mut reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|_| ApiError::BadRequest(String::from("无法识别的图片")))?;
+    if !matches!(
+        reader.format(),
+        Some(ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP)
+    ) {
+        return Err(ApiError::BadRequest(String::from(
+            "头像只支持 PNG、JPEG 或 WebP 格式",
+        )));
+    }
+    let mut limits = image::Limits::default();
+    limits.max_image_width = Some(AVATAR_MAX_DIMENSION);
+    limits.max_image_height = Some(AVATAR_MAX_DIMENSION);
+    limits.max_alloc = Some(AVATAR_MAX_ALLOC);
+    reader.limits(limits);
+    let image = reader.decode().map_err(|err| match err {
+        ImageError::Limits(_) => ApiError::BadRequest(String::from("图片尺寸过大")),
+        _ => ApiError::BadRequest(String::from("图片已损坏")),
+    })?;
+    let thumbnail = image.resize_to_fill(*AVATAR_SIZE, *AVATAR_SIZE, FilterType::Lanczos3);
+    let mut buffer = Cursor::new(Vec::new());
+    thumbnail.write_to(&mut buffer, ImageFormat::Png)?;
+    Ok(buffer.into_inner())
+}
@@ -0,0 +1,37 @@
+use lru::LruCache;
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// An LRU cache whose entries expire after `ttl`, so changes made by other
+/// instances are picked up without hitting the database on every request.
+pub struct TtlCache<K, V> {
+    entries: Mutex<LruCache<K, (V, Instant)>>,
+    ttl: Duration,
+}
+
+impl<K: Hash + Eq, V: Copy> TtlCache<K, V> {
+    pub fn new(size: usize, ttl: u64) -> Self {
+        let size = NonZeroUsize::new(size).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            entries: Mutex::new(LruCache::new(size)),
+            ttl: Duration::from_secs(ttl),
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some((value, cached_at)) if cached_at.elapsed() < self.ttl => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn put(&self, key: K, value: V) {
+        self.entries
+            .lock()
+            .unwrap()
+            .put(key, (value, Instant::now()));
+    }
+}
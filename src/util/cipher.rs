@@ -0,0 +1,98 @@
+use crate::error::api::ApiError;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{Context, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::env;
+
+lazy_static! {
+    pub static ref SESSION_KEY_CIPHER: Cipher =
+        Cipher::from_env().expect("invalid session key configuration");
+}
+
+const PREFIX: &str = "enc";
+const NONCE_LENGTH: usize = 12;
+
+/// Seals short secrets as `enc:<kid>:<base64(nonce || ciphertext)>` with
+/// AES-256-GCM. Retired keys stay available for opening so existing rows can
+/// be re-sealed under the current key.
+pub struct Cipher {
+    kid: String,
+    keys: HashMap<String, Aes256Gcm>,
+}
+
+impl Cipher {
+    /// Reads `SESSION_KEY_ID`, `SESSION_KEY_SECRET` (base64, 32 bytes) and the
+    /// optional `SESSION_KEY_RETIRED` list of `kid=secret` pairs.
+    fn from_env() -> anyhow::Result<Cipher> {
+        let kid = env::var("SESSION_KEY_ID").unwrap_or_else(|_| "1".to_owned());
+        let secret = env::var("SESSION_KEY_SECRET").context("SESSION_KEY_SECRET must be set")?;
+        let mut keys = HashMap::new();
+        keys.insert(kid.clone(), Cipher::key(&secret)?);
+        if let Ok(retired) = env::var("SESSION_KEY_RETIRED") {
+            for entry in retired.split(',').filter(|entry| !entry.trim().is_empty()) {
+                let (retired_kid, secret) = entry
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("invalid SESSION_KEY_RETIRED entry: {}", entry))?;
+                keys.entry(retired_kid.trim().to_owned())
+                    .or_insert(Cipher::key(secret.trim())?);
+            }
+        }
+        Ok(Cipher { kid, keys })
+    }
+
+    fn key(secret: &str) -> anyhow::Result<Aes256Gcm> {
+        let secret = STANDARD
+            .decode(secret)
+            .context("session key secret is not base64")?;
+        if secret.len() != 32 {
+            return Err(anyhow!("session key secret must be 32 bytes"));
+        }
+        Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&secret)))
+    }
+
+    pub fn seal(&self, plain: &str) -> Result<String, ApiError> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self.keys[&self.kid]
+            .encrypt(&nonce, plain.as_bytes())
+            .map_err(|_| anyhow!("session key encryption failed"))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(format!(
+            "{}:{}:{}",
+            PREFIX,
+            self.kid,
+            STANDARD.encode(sealed)
+        ))
+    }
+
+    pub fn open(&self, sealed: &str) -> Result<String, ApiError> {
+        let invalid = || ApiError::from(anyhow!("session key decryption failed"));
+        let Some((PREFIX, rest)) = sealed.split_once(':') else {
+            return Err(invalid());
+        };
+        let (kid, data) = rest.split_once(':').ok_or_else(invalid)?;
+        let key = self.keys.get(kid).ok_or_else(invalid)?;
+        let data = STANDARD.decode(data).map_err(|_| invalid())?;
+        if data.len() <= NONCE_LENGTH {
+            return Err(invalid());
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LENGTH);
+        let plain = key
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| invalid())?;
+        String::from_utf8(plain).map_err(|_| invalid())
+    }
+
+    pub fn is_sealed(value: &str) -> bool {
+        value.starts_with(&format!("{}:", PREFIX))
+    }
+
+    /// Prefix shared by every value sealed under the current key.
+    pub fn current_prefix(&self) -> String {
+        format!("{}:{}:", PREFIX, self.kid)
+    }
+}
@@ -0,0 +1,27 @@
+use rocket::Request;
+use rocket::request::{FromRequest, Outcome};
+use std::convert::Infallible;
+
+const USER_AGENT_LENGTH: usize = 255;
+
+/// Where a request came from, kept with sign-ins for the login history.
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientInfo {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(ClientInfo {
+            ip: request.client_ip().map(|ip| ip.to_string()),
+            user_agent: request
+                .headers()
+                .get_one("User-Agent")
+                .map(|user_agent| user_agent.chars().take(USER_AGENT_LENGTH).collect()),
+        })
+    }
+}
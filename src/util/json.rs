@@ -0,0 +1,69 @@
+use crate::util::timezone::{self, RequestTimezone};
+use rocket::Request;
+use rocket::data::{Data, FromData, Limits, Outcome};
+use rocket::http::Status;
+use rocket::response::{self, Responder, content};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::ops::{Deref, DerefMut};
+
+/// Drop-in for `rocket::serde::json::Json` that (de)serializes timestamps in
+/// the requesting user's timezone.
+#[derive(Debug)]
+pub struct Json<T>(pub T);
+
+impl<T> Json<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Json<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Json<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T: DeserializeOwned> FromData<'r> for Json<T> {
+    type Error = anyhow::Error;
+
+    async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> Outcome<'r, Self> {
+        let limit = request.limits().get("json").unwrap_or(Limits::JSON);
+        let body = match data.open(limit).into_string().await {
+            Ok(body) if body.is_complete() => body.into_inner(),
+            Ok(_) => {
+                return Outcome::Error((
+                    Status::PayloadTooLarge,
+                    anyhow::anyhow!("body too large"),
+                ));
+            }
+            Err(err) => return Outcome::Error((Status::BadRequest, err.into())),
+        };
+        let timezone = RequestTimezone::of(request);
+        match timezone::scope(timezone, || serde_json::from_str::<T>(&body)) {
+            Ok(value) => Outcome::Success(Json(value)),
+            Err(err) if err.is_data() => Outcome::Error((Status::UnprocessableEntity, err.into())),
+            Err(err) => Outcome::Error((Status::BadRequest, err.into())),
+        }
+    }
+}
+
+impl<'r, T: Serialize> Responder<'r, 'static> for Json<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let timezone = RequestTimezone::of(request);
+        let body = timezone::scope(timezone, || serde_json::to_string(&self.0)).map_err(|err| {
+            error!("failed to serialize response: {}", err);
+            Status::InternalServerError
+        })?;
+        content::RawJson(body).respond_to(request)
+    }
+}
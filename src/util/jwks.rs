@@ -0,0 +1,79 @@
+use anyhow::{Context, anyhow, bail};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::Algorithm;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, KeyAlgorithm,
+    OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use simple_asn1::{ASN1Block, from_der};
+
+/// Builds the public JWK for a PEM encoded verification key. RSA keys may be
+/// either SPKI (`PUBLIC KEY`) or PKCS#1 (`RSA PUBLIC KEY`); Ed25519 keys must
+/// be SPKI.
+pub fn public_jwk(algorithm: Algorithm, kid: &str, pem: &[u8]) -> anyhow::Result<Jwk> {
+    let pem = pem::parse(pem).context("invalid PEM")?;
+    let (key_algorithm, parameters) = match algorithm {
+        Algorithm::RS256 => {
+            let der = match pem.tag() {
+                "RSA PUBLIC KEY" => pem.contents().to_vec(),
+                "PUBLIC KEY" => spki_key(pem.contents())?,
+                tag => bail!("unexpected PEM tag: {}", tag),
+            };
+            let (n, e) = rsa_components(&der)?;
+            (
+                KeyAlgorithm::RS256,
+                AlgorithmParameters::RSA(RSAKeyParameters {
+                    key_type: RSAKeyType::RSA,
+                    n: URL_SAFE_NO_PAD.encode(n),
+                    e: URL_SAFE_NO_PAD.encode(e),
+                }),
+            )
+        }
+        Algorithm::EdDSA => {
+            if pem.tag() != "PUBLIC KEY" {
+                bail!("unexpected PEM tag: {}", pem.tag());
+            }
+            (
+                KeyAlgorithm::EdDSA,
+                AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                    key_type: OctetKeyPairType::OctetKeyPair,
+                    curve: EllipticCurve::Ed25519,
+                    x: URL_SAFE_NO_PAD.encode(spki_key(pem.contents())?),
+                }),
+            )
+        }
+        algorithm => bail!("unsupported algorithm: {:?}", algorithm),
+    };
+    Ok(Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            key_algorithm: Some(key_algorithm),
+            key_id: Some(kid.to_owned()),
+            ..Default::default()
+        },
+        algorithm: parameters,
+    })
+}
+
+fn spki_key(der: &[u8]) -> anyhow::Result<Vec<u8>> {
+    match from_der(der)?.as_slice() {
+        [ASN1Block::Sequence(_, fields)] => match fields.as_slice() {
+            [ASN1Block::Sequence(_, _), ASN1Block::BitString(_, _, key)] => Ok(key.clone()),
+            _ => Err(anyhow!("invalid SubjectPublicKeyInfo")),
+        },
+        _ => Err(anyhow!("invalid SubjectPublicKeyInfo")),
+    }
+}
+
+fn rsa_components(der: &[u8]) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
+    match from_der(der)?.as_slice() {
+        [ASN1Block::Sequence(_, fields)] => match fields.as_slice() {
+            [ASN1Block::Integer(_, n), ASN1Block::Integer(_, e)] => {
+                Ok((n.to_bytes_be().1, e.to_bytes_be().1))
+            }
+            _ => Err(anyhow!("invalid RSA public key")),
+        },
+        _ => Err(anyhow!("invalid RSA public key")),
+    }
+}
@@ -1,12 +1,24 @@
+use crate::db::BpRecordConn;
+use crate::db::revocation::{RevokedTokens, TokenAccount};
 use crate::error::api::ApiError;
 use crate::error::auth::AuthError;
-use jsonwebtoken::{decode, DecodingKey, EncodingKey, Validation};
+use crate::util::jwks::public_jwk;
+use anyhow::{Context, bail};
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation, decode, decode_header,
+    encode, errors::ErrorKind,
+};
 use lazy_static::lazy_static;
+use rocket::Request;
+use rocket::fairing::AdHoc;
 use rocket::form::{FromFormField, ValueField};
+use rocket::http::Status;
 use rocket::request::{FromParam, FromRequest, Outcome};
-use rocket::Request;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
+use std::fs;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
@@ -17,43 +29,200 @@ lazy_static! {
             .parse::<u64>()
             .unwrap()
     };
-    pub static ref KEYS: Keys = {
-        let secret = env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-        Keys::new(secret.as_bytes())
+    pub static ref JWT_ISSUER: String =
+        env::var("JWT_ISSUER").unwrap_or_else(|_| "bp-service".to_owned());
+    pub static ref JWT_AUDIENCE: String =
+        env::var("JWT_AUDIENCE").unwrap_or_else(|_| "bp-record".to_owned());
+    pub static ref JWT_LEEWAY: u64 = {
+        env::var("JWT_LEEWAY")
+            .unwrap_or_else(|_| "60".to_owned())
+            .parse::<u64>()
+            .unwrap()
+    };
+    pub static ref JWT_ALGORITHM: Algorithm = {
+        env::var("JWT_ALGORITHM")
+            .unwrap_or_else(|_| "HS256".to_owned())
+            .parse::<Algorithm>()
+            .expect("JWT_ALGORITHM must be HS256, RS256 or EdDSA")
+    };
+    pub static ref KEYS: Keys = match *JWT_ALGORITHM {
+        Algorithm::HS256 => {
+            let secret = env::var("JWT_SECRET").expect("JWT_SECRET must be set");
+            Keys::new(secret.as_bytes())
+        }
+        algorithm => Keys::from_env(algorithm).expect("invalid JWT key configuration"),
     };
 }
 
+pub struct VerificationKey {
+    pub decoding: DecodingKey,
+    pub jwk: Jwk,
+}
+
 pub struct Keys {
+    pub algorithm: Algorithm,
+    pub kid: Option<String>,
     pub encoding: EncodingKey,
-    pub decoding: DecodingKey,
+    pub decoding: Option<DecodingKey>,
+    pub verification: HashMap<String, VerificationKey>,
 }
 
 impl Keys {
     pub fn new(secret: &[u8]) -> Self {
         Self {
+            algorithm: Algorithm::HS256,
+            kid: None,
             encoding: EncodingKey::from_secret(secret),
-            decoding: DecodingKey::from_secret(secret),
+            decoding: Some(DecodingKey::from_secret(secret)),
+            verification: HashMap::new(),
+        }
+    }
+
+    /// Loads the signing key from `JWT_PRIVATE_KEY` and the verification keys
+    /// from `JWT_PUBLIC_KEYS` (`kid=path` pairs separated by commas). Keys
+    /// that are being rotated out stay in `JWT_PUBLIC_KEYS` until every token
+    /// they signed has expired.
+    fn from_env(algorithm: Algorithm) -> anyhow::Result<Self> {
+        let kid = env::var("JWT_KEY_ID").context("JWT_KEY_ID must be set")?;
+        let private_key =
+            fs::read(env::var("JWT_PRIVATE_KEY").context("JWT_PRIVATE_KEY must be set")?)?;
+        let encoding = match algorithm {
+            Algorithm::RS256 => EncodingKey::from_rsa_pem(&private_key)?,
+            Algorithm::EdDSA => EncodingKey::from_ed_pem(&private_key)?,
+            algorithm => bail!("unsupported algorithm: {:?}", algorithm),
+        };
+        let mut verification = HashMap::new();
+        let public_keys = env::var("JWT_PUBLIC_KEYS").context("JWT_PUBLIC_KEYS must be set")?;
+        for entry in public_keys
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+        {
+            let (key_id, path) = entry
+                .split_once('=')
+                .with_context(|| format!("invalid JWT_PUBLIC_KEYS entry: {}", entry))?;
+            let key_id = key_id.trim();
+            let public_key = fs::read(path.trim())?;
+            let decoding = match algorithm {
+                Algorithm::RS256 => DecodingKey::from_rsa_pem(&public_key)?,
+                _ => DecodingKey::from_ed_pem(&public_key)?,
+            };
+            let jwk = public_jwk(algorithm, key_id, &public_key)?;
+            verification.insert(key_id.to_owned(), VerificationKey { decoding, jwk });
+        }
+        if !verification.contains_key(&kid) {
+            bail!("JWT_PUBLIC_KEYS must contain the signing key {}", kid);
         }
+        Ok(Self {
+            algorithm,
+            kid: Some(kid),
+            encoding,
+            decoding: None,
+            verification,
+        })
+    }
+
+    pub fn encode(&self, claims: &Claims) -> Result<String, AuthError> {
+        let mut header = Header::new(self.algorithm);
+        header.kid = self.kid.clone();
+        encode(&header, claims, &self.encoding).map_err(|_| AuthError::TokenCreation)
+    }
+
+    pub fn decode(&self, token: &str) -> Result<TokenData<Claims>, AuthError> {
+        let header = decode_header(token).map_err(|_| AuthError::MalformedToken)?;
+        let decoding = match (&self.decoding, header.kid) {
+            (Some(decoding), _) => decoding,
+            (None, Some(kid)) => match self.verification.get(&kid) {
+                Some(key) => &key.decoding,
+                None => return Err(AuthError::UnknownKey),
+            },
+            (None, None) => return Err(AuthError::UnknownKey),
+        };
+        let token_data = decode::<Claims>(token, decoding, &self.validation()).map_err(|err| {
+            match err.kind() {
+                ErrorKind::ExpiredSignature => AuthError::ExpiredToken,
+                ErrorKind::ImmatureSignature => AuthError::ImmatureToken,
+                ErrorKind::InvalidSignature | ErrorKind::InvalidAlgorithm => {
+                    AuthError::InvalidSignature
+                }
+                ErrorKind::InvalidIssuer => AuthError::InvalidIssuer,
+                ErrorKind::InvalidAudience => AuthError::InvalidAudience,
+                ErrorKind::InvalidToken
+                | ErrorKind::Base64(_)
+                | ErrorKind::Json(_)
+                | ErrorKind::Utf8(_)
+                | ErrorKind::MissingRequiredClaim(_) => AuthError::MalformedToken,
+                _ => AuthError::InvalidToken,
+            }
+        })?;
+        if token_data.claims.iat > timestamp(Duration::from_secs(*JWT_LEEWAY)) {
+            return Err(AuthError::ImmatureToken);
+        }
+        Ok(token_data)
+    }
+
+    fn validation(&self) -> Validation {
+        let mut validation = Validation::new(self.algorithm);
+        validation.set_required_spec_claims(&["exp", "nbf", "iat", "iss", "aud", "sub"]);
+        validation.set_issuer(&[JWT_ISSUER.as_str()]);
+        validation.set_audience(&[JWT_AUDIENCE.as_str()]);
+        validation.validate_nbf = true;
+        validation.leeway = *JWT_LEEWAY;
+        validation
+    }
+
+    pub fn jwks(&self) -> JwkSet {
+        let mut keys = self
+            .verification
+            .values()
+            .map(|key| key.jwk.clone())
+            .collect::<Vec<_>>();
+        keys.sort_by(|a, b| a.common.key_id.cmp(&b.common.key_id));
+        JwkSet { keys }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
+    pub iss: String,
+    pub aud: String,
     pub exp: i64,
+    pub nbf: i64,
+    pub iat: i64,
+    pub jti: Uuid,
+    pub generation: i32,
 }
 
 impl Claims {
-    pub fn new(sub: String) -> Self {
-        let exp = SystemTime::now() + Duration::from_secs(*JWT_EXPIRE);
-        let exp = exp.duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
-        Claims { sub, exp }
+    pub fn new(sub: String, generation: i32) -> Self {
+        let now = timestamp(Duration::ZERO);
+        Claims {
+            sub,
+            iss: JWT_ISSUER.clone(),
+            aud: JWT_AUDIENCE.clone(),
+            exp: timestamp(Duration::from_secs(*JWT_EXPIRE)),
+            nbf: now,
+            iat: now,
+            jti: Uuid::new_v4(),
+            generation,
+        }
     }
 }
 
+fn timestamp(offset: Duration) -> i64 {
+    let time = SystemTime::now() + offset;
+    time.duration_since(UNIX_EPOCH).unwrap().as_secs() as i64
+}
+
 #[derive(Debug)]
 pub struct Uid(pub Uuid);
 
+/// A signed-in user with the admin role. The role is not carried in the
+/// token; it is read with the token generation, so a change takes effect
+/// once the cached entry expires after `REVOCATION_CACHE_TTL` seconds.
+#[derive(Debug)]
+pub struct Admin(pub Uuid);
+
 impl From<Uid> for Uuid {
     fn from(Uid(uuid): Uid) -> Self {
         uuid
@@ -63,7 +232,7 @@ impl From<Uid> for Uuid {
 impl<'a> FromFormField<'a> for Uid {
     fn from_value(field: ValueField<'a>) -> rocket::form::Result<'a, Self> {
         Uuid::parse_str(field.value)
-            .map(|uuid| Uid(uuid))
+            .map(Uid)
             .map_err(|_| rocket::form::Error::validation("Invalid Uuid").into())
     }
 }
@@ -73,34 +242,114 @@ impl<'a> FromParam<'a> for Uid {
 
     fn from_param(param: &'a str) -> Result<Self, Self::Error> {
         Uuid::parse_str(param)
-            .map(|uuid| Uid(uuid))
+            .map(Uid)
             .map_err(|_| ApiError::BadRequest(String::from("Invalid Uuid")))
     }
 }
 
+/// Remembers why authentication failed so the 401 catcher can report it;
+/// Rocket discards the error carried by a failed guard outcome.
+fn failure<T>(request: &Request<'_>, status: Status, err: AuthError) -> Outcome<T, AuthError> {
+    request.local_cache(|| Some(err));
+    Outcome::Error((status, err))
+}
+
+/// Result of verifying the bearer token, kept in the request-local cache so
+/// `Claims`, `Uid`, `Admin` and the fairings share a single verification.
+struct Verification(Result<(Claims, TokenAccount), (Status, AuthError)>);
+
+async fn verification<'r>(request: &'r Request<'_>) -> &'r Verification {
+    request
+        .local_cache_async(async { Verification(verify(request).await) })
+        .await
+}
+
+async fn verify(request: &Request<'_>) -> Result<(Claims, TokenAccount), (Status, AuthError)> {
+    let Some(header) = request.headers().get_one("Authorization") else {
+        return Err((Status::Unauthorized, AuthError::MissingCredentials));
+    };
+    let Some(token) = header.strip_prefix("Bearer ") else {
+        return Err((Status::Unauthorized, AuthError::WrongCredentials));
+    };
+    let claims = KEYS
+        .decode(token)
+        .map_err(|err| (Status::Unauthorized, err))?
+        .claims;
+    let Ok(user_id) = claims.sub.parse::<Uuid>() else {
+        return Err((Status::Unauthorized, AuthError::InvalidSubject));
+    };
+    let account = match RevokedTokens::cached(user_id, &claims) {
+        Some(account) => account,
+        None => {
+            let Outcome::Success(conn) = request.guard::<BpRecordConn>().await else {
+                return Err((Status::ServiceUnavailable, AuthError::InvalidToken));
+            };
+            RevokedTokens::check(&conn, user_id, &claims)
+                .await
+                .map_err(|_| (Status::InternalServerError, AuthError::InvalidToken))?
+        }
+    };
+    let Some(account) = account else {
+        return Err((Status::Unauthorized, AuthError::RevokedToken));
+    };
+    Ok((claims, account))
+}
+
+/// Verifies the bearer token before routing. A revocation lookup that misses
+/// the cache then takes its connection while the request holds none, rather
+/// than waiting for a second one behind the handler's `BpRecordConn`.
+pub fn fairing() -> AdHoc {
+    AdHoc::on_request("Authentication", |request, _| {
+        Box::pin(async move {
+            if request.headers().contains("Authorization") {
+                let _ = request.guard::<Claims>().await;
+            }
+        })
+    })
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Claims {
+    type Error = AuthError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Verification(verification) = verification(request).await;
+        match verification {
+            Ok((claims, _)) => Outcome::Success(claims.clone()),
+            Err((status, err)) => failure(request, *status, *err),
+        }
+    }
+}
+
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for Uid {
     type Error = AuthError;
 
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        if let Some(header) = request.headers().get_one("Authorization") {
-            if let Some(token) = header.strip_prefix("Bearer ") {
-                let token_data = decode::<Claims>(token, &KEYS.decoding, &Validation::default());
-                if let Ok(token_data) = token_data {
-                    let user_id = token_data.claims.sub.parse::<Uuid>();
-                    if let Ok(uuid) = user_id {
-                        Outcome::Success(Uid(uuid))
-                    } else {
-                        Outcome::Error(AuthError::WrongCredentials.into())
-                    }
-                } else {
-                    Outcome::Error(AuthError::InvalidToken.into())
-                }
-            } else {
-                Outcome::Error(AuthError::WrongCredentials.into())
-            }
-        } else {
-            Outcome::Error(AuthError::MissingCredentials.into())
+        match request.guard::<Claims>().await {
+            Outcome::Success(claims) => match claims.sub.parse::<Uuid>() {
+                Ok(uuid) => Outcome::Success(Uid(uuid)),
+                Err(_) => failure(request, Status::Unauthorized, AuthError::InvalidSubject),
+            },
+            Outcome::Error(err) => Outcome::Error(err),
+            Outcome::Forward(status) => Outcome::Forward(status),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = AuthError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let user_id = match request.guard::<Uid>().await {
+            Outcome::Success(Uid(user_id)) => user_id,
+            Outcome::Error(err) => return Outcome::Error(err),
+            Outcome::Forward(status) => return Outcome::Forward(status),
+        };
+        match verification(request).await {
+            Verification(Ok((_, account))) if account.admin => Outcome::Success(Admin(user_id)),
+            _ => failure(request, Status::Forbidden, AuthError::AdminRequired),
         }
     }
 }
@@ -1,2 +1,11 @@
+pub mod avatar;
+pub mod cache;
+pub mod cipher;
+pub mod client;
+pub mod json;
+pub mod jwks;
 pub mod jwt;
-pub mod serde_time_format;
\ No newline at end of file
+pub mod rate_limit;
+pub mod secret;
+pub mod serde_time_format;
+pub mod timezone;
@@ -0,0 +1,37 @@
+use crate::error::api::ApiError;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub struct RateLimiter {
+    max: usize,
+    window: Duration,
+    hits: Mutex<HashMap<String, Vec<Instant>>>,
+}
+
+impl RateLimiter {
+    pub fn new(max: usize, window: Duration) -> Self {
+        Self {
+            max,
+            window,
+            hits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn check(&self, key: &str) -> Result<(), ApiError> {
+        let now = Instant::now();
+        let mut hits = self.hits.lock().unwrap();
+        hits.retain(|_, times| {
+            times.retain(|time| now.duration_since(*time) < self.window);
+            !times.is_empty()
+        });
+        let times = hits.entry(key.to_owned()).or_default();
+        if times.len() >= self.max {
+            return Err(ApiError::TooManyRequests(String::from(
+                "操作过于频繁，请稍后再试",
+            )));
+        }
+        times.push(now);
+        Ok(())
+    }
+}
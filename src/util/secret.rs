@@ -0,0 +1,32 @@
+use rand::distributions::{Alphanumeric, Slice};
+use rand::{Rng, thread_rng};
+use sha2::{Digest, Sha256};
+
+const CODE_CHARSET: &[char] = &[
+    'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'J', 'K', 'L', 'M', 'N', 'P', 'Q', 'R', 'S', 'T', 'U',
+    'V', 'W', 'X', 'Y', 'Z', '2', '3', '4', '5', '6', '7', '8', '9',
+];
+
+pub fn random_code(len: usize) -> String {
+    let charset = Slice::new(CODE_CHARSET).unwrap();
+    thread_rng().sample_iter(charset).take(len).collect()
+}
+
+pub fn random_digits(len: usize) -> String {
+    let mut rng = thread_rng();
+    (0..len)
+        .map(|_| char::from(b'0' + rng.gen_range(0..10)))
+        .collect()
+}
+
+pub fn random_token(len: usize) -> String {
+    thread_rng()
+        .sample_iter(Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+pub fn hash_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
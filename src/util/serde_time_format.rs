@@ -1,18 +1,30 @@
-use chrono::{Local, NaiveDateTime, Utc};
+use crate::util::timezone;
+use chrono::{NaiveDateTime, Utc};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-const FORMAT: &'static str = "%Y-%m-%d %H:%M:%S";
+const FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Timestamps are stored in UTC and shown in the requesting user's timezone,
+/// see `timezone::scope`.
+fn format(date: &NaiveDateTime) -> String {
+    date.and_utc()
+        .with_timezone(&timezone::current())
+        .format(FORMAT)
+        .to_string()
+}
+
+fn to_utc<E: serde::de::Error>(date: NaiveDateTime) -> Result<NaiveDateTime, E> {
+    date.and_local_timezone(timezone::current())
+        .earliest()
+        .map(|date| date.with_timezone(&Utc).naive_utc())
+        .ok_or_else(|| E::custom("Invalid time format."))
+}
 
 pub fn serialize<S>(date: &NaiveDateTime, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
-    let dt_utc = date.and_local_timezone(Utc).unwrap();
-    dt_utc
-        .with_timezone(&Local)
-        .format(FORMAT)
-        .to_string()
-        .serialize(serializer)
+    format(date).serialize(serializer)
 }
 
 pub fn deserialize<'de, D>(deserializer: D) -> Result<NaiveDateTime, D::Error>
@@ -20,8 +32,7 @@ where
     D: Deserializer<'de>,
 {
     if let Ok(date) = NaiveDateTime::deserialize(deserializer) {
-        let dt_local = date.and_local_timezone(Local).unwrap();
-        Ok(dt_local.with_timezone(&Utc).naive_utc())
+        to_utc(date)
     } else {
         Err(serde::de::Error::custom("Invalid time format."))
     }
@@ -34,12 +45,7 @@ pub mod optional {
         S: Serializer,
     {
         if let Some(date) = opt {
-            let dt_utc = date.and_local_timezone(Utc).unwrap();
-            dt_utc
-                .with_timezone(&Local)
-                .format(FORMAT)
-                .to_string()
-                .serialize(serializer)
+            format(date).serialize(serializer)
         } else {
             serializer.serialize_none()
         }
@@ -50,8 +56,7 @@ pub mod optional {
         D: Deserializer<'de>,
     {
         if let Ok(Some(date)) = Option::<NaiveDateTime>::deserialize(deserializer) {
-            let dt_local = date.and_local_timezone(Local).unwrap();
-            Ok(Some(dt_local.with_timezone(&Utc).naive_utc()))
+            to_utc(date).map(Some)
         } else {
             Ok(None)
         }
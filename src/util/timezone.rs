@@ -0,0 +1,72 @@
+use crate::db::BpRecordConn;
+use crate::db::profile::UserProfiles;
+use crate::util::jwt::Uid;
+use chrono_tz::Tz;
+use lazy_static::lazy_static;
+use rocket::Request;
+use rocket::fairing::AdHoc;
+use std::cell::Cell;
+use std::env;
+
+lazy_static! {
+    pub static ref DEFAULT_TIMEZONE: Tz = {
+        env::var("DEFAULT_TIMEZONE")
+            .unwrap_or_else(|_| "Asia/Shanghai".to_owned())
+            .parse::<Tz>()
+            .unwrap()
+    };
+}
+
+thread_local! {
+    static CURRENT: Cell<Option<Tz>> = const { Cell::new(None) };
+}
+
+/// The timezone of the user making the request, resolved once by `fairing`
+/// and read back when JSON bodies are parsed or rendered.
+pub struct RequestTimezone(pub Option<Tz>);
+
+impl RequestTimezone {
+    pub fn of(request: &Request<'_>) -> Option<Tz> {
+        request.local_cache(|| RequestTimezone(None)).0
+    }
+}
+
+/// Timezone used by `serde_time_format` on this thread.
+pub fn current() -> Tz {
+    CURRENT.with(Cell::get).unwrap_or(*DEFAULT_TIMEZONE)
+}
+
+/// Runs `f` with `timezone` as the current one. Serde callbacks cannot take
+/// extra arguments, so the timezone is passed through a thread-local that is
+/// only set around synchronous (de)serialization.
+pub fn scope<T>(timezone: Option<Tz>, f: impl FnOnce() -> T) -> T {
+    let previous = CURRENT.with(|current| current.replace(timezone));
+    let result = f();
+    CURRENT.with(|current| current.set(previous));
+    result
+}
+
+/// Resolves the signed-in user's timezone. The token verification cached by
+/// `jwt::fairing` is reused, so attach this after it.
+pub fn fairing() -> AdHoc {
+    AdHoc::on_request("Request Timezone", |request, _| {
+        Box::pin(async move {
+            let Some(Uid(user_id)) = request.guard::<Uid>().await.succeeded() else {
+                return;
+            };
+            if let Some(timezone) = UserProfiles::cached_timezone(user_id) {
+                request.local_cache(|| RequestTimezone(Some(timezone)));
+                return;
+            }
+            let Some(conn) = request.guard::<BpRecordConn>().await.succeeded() else {
+                return;
+            };
+            match UserProfiles::timezone(&conn, user_id).await {
+                Ok(timezone) => {
+                    request.local_cache(|| RequestTimezone(Some(timezone)));
+                }
+                Err(err) => error!("request timezone: {:?}", err),
+            }
+        })
+    })
+}
@@ -0,0 +1,156 @@
+use crate::error::api::ApiError;
+use aes::Aes128;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use cbc::cipher::block_padding::Pkcs7;
+use cbc::cipher::{BlockDecryptMut, KeyIvInit};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+type Aes128CbcDec = cbc::Decryptor<Aes128>;
+
+#[derive(Deserialize)]
+pub struct Watermark {
+    pub appid: String,
+    pub timestamp: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WxPhone {
+    pub phone_number: String,
+    pub pure_phone_number: String,
+    pub country_code: String,
+    pub watermark: Watermark,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WxUserInfo {
+    pub nick_name: String,
+    #[serde(default)]
+    pub avatar_url: Option<String>,
+    pub watermark: Watermark,
+}
+
+trait Watermarked {
+    fn watermark(&self) -> &Watermark;
+}
+
+impl Watermarked for WxPhone {
+    fn watermark(&self) -> &Watermark {
+        &self.watermark
+    }
+}
+
+impl Watermarked for WxUserInfo {
+    fn watermark(&self) -> &Watermark {
+        &self.watermark
+    }
+}
+
+fn invalid() -> ApiError {
+    ApiError::BadRequest(String::from("微信数据解密失败"))
+}
+
+/// Decrypts `encryptedData` returned by the mini program with the user's
+/// `session_key` (AES-128-CBC, PKCS#7, all values base64) and rejects data
+/// whose watermark was issued for another app.
+fn decrypt<T: DeserializeOwned + Watermarked>(
+    session_key: &str,
+    encrypted_data: &str,
+    iv: &str,
+    app_id: &str,
+) -> Result<T, ApiError> {
+    let key = STANDARD.decode(session_key).map_err(|_| invalid())?;
+    let iv = STANDARD.decode(iv).map_err(|_| invalid())?;
+    let data = STANDARD.decode(encrypted_data).map_err(|_| invalid())?;
+    let plain = Aes128CbcDec::new_from_slices(&key, &iv)
+        .map_err(|_| invalid())?
+        .decrypt_padded_vec_mut::<Pkcs7>(&data)
+        .map_err(|_| invalid())?;
+    let decrypted = serde_json::from_slice::<T>(&plain).map_err(|_| invalid())?;
+    if decrypted.watermark().appid != app_id {
+        return Err(invalid());
+    }
+    Ok(decrypted)
+}
+
+pub fn decrypt_phone(
+    session_key: &str,
+    encrypted_data: &str,
+    iv: &str,
+    app_id: &str,
+) -> Result<WxPhone, ApiError> {
+    decrypt(session_key, encrypted_data, iv, app_id)
+}
+
+pub fn decrypt_user_info(
+    session_key: &str,
+    encrypted_data: &str,
+    iv: &str,
+    app_id: &str,
+) -> Result<WxUserInfo, ApiError> {
+    decrypt(session_key, encrypted_data, iv, app_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cbc::cipher::BlockEncryptMut;
+    use cbc::cipher::block_padding::NoPadding;
+
+    // Sample from the WeChat "open data" decryption documentation.
+    const APP_ID: &str = "wx4f4bc4dec97d474b";
+    const SESSION_KEY: &str = "tiihtNczf5v6AKRyjwEUhQ==";
+    const ENCRYPTED_DATA: &str = "CiyLU1Aw2KjvrjMdj8YKliAjtP4gsMZMQmRzooG2xrDcvSnxIMXFufNstNGTyaGS9uT5geRa0W4oTOb1WT7fJlAC+oNPdbB+3hVbJSRgv+4lGOETKUQz6OYStslQ142dNCuabNPGBzlooOmB231qMM85d2/fV6ChevvXvQP8Hkue1poOFtnEtpyxVLW1zAo6/1Xx1COxFvrc2d7UL/lmHInNlxuacJXwu0fjpXfz/YqYzBIBzD6WUfTIF9GRHpOn/Hz7saL8xz+W//FRAUid1OksQaQx4CMs8LOddcQhULW4ucetDf96JcR3g0gfRK4PC7E/r7Z6xNrXd2UIeorGj5Ef7b1pJAYB6Y5anaHqZ9J6nKEBvB4DnNLIVWSgARns/8wR2SiRS7MNACwTyrGvt9ts8p12PKFdlqYTopNHR1Vf7XjfhQlVsAJdNiKdYmYVoKlaRv85IfVunYzO0IKXsyl7JCUjCpoG20f0a04COwfneQAGGwd5oa+T8yO5hzuyDb/XcxxmK01EpqOyuxINew==";
+    const IV: &str = "r7BXXKkLb8qrSNn05n0qiA==";
+    const DECRYPTED: &str = r#"{"openId":"oGZUI0egBJY1zhBYw2KhdUfwVJJE","nickName":"Band","gender":1,"language":"zh_CN","city":"Guangzhou","province":"Guangdong","country":"CN","avatarUrl":"http://wx.qlogo.cn/mmopen/vi_32/aSKcBBPpibyKNicHNTMM0qJVh8Kjgiak2AHWr8MHM4WgMEm7GFhsf8OYrySdbvAMvTsw3mo8ibKicsnfN5pRjl1p8HQ/0","unionId":"ocMvos6NjeKLIBqg5Mr9QjxrP1FA","watermark":{"timestamp":1477314187,"appid":"wx4f4bc4dec97d474b"}}"#;
+
+    #[test]
+    fn decrypts_official_sample() {
+        let key = STANDARD.decode(SESSION_KEY).unwrap();
+        let iv = STANDARD.decode(IV).unwrap();
+        let plain = Aes128CbcDec::new_from_slices(&key, &iv)
+            .unwrap()
+            .decrypt_padded_vec_mut::<Pkcs7>(&STANDARD.decode(ENCRYPTED_DATA).unwrap())
+            .unwrap();
+        assert_eq!(String::from_utf8(plain).unwrap(), DECRYPTED);
+
+        let Ok(user_info) = decrypt_user_info(SESSION_KEY, ENCRYPTED_DATA, IV, APP_ID) else {
+            panic!("failed to decrypt the official sample");
+        };
+        assert_eq!(user_info.nick_name, "Band");
+        assert_eq!(user_info.watermark.appid, APP_ID);
+        assert_eq!(user_info.watermark.timestamp, 1477314187);
+    }
+
+    #[test]
+    fn rejects_watermark_of_another_app() {
+        assert!(decrypt_user_info(SESSION_KEY, ENCRYPTED_DATA, IV, "wx0000000000000000").is_err());
+    }
+
+    #[test]
+    fn rejects_bad_padding() {
+        let key = STANDARD.decode(SESSION_KEY).unwrap();
+        let iv = STANDARD.decode(IV).unwrap();
+        // One full block ending in 'f' (0x66) is not valid PKCS#7 padding.
+        let data = cbc::Encryptor::<Aes128>::new_from_slices(&key, &iv)
+            .unwrap()
+            .encrypt_padded_vec_mut::<NoPadding>(b"0123456789abcdef");
+        assert!(
+            Aes128CbcDec::new_from_slices(&key, &iv)
+                .unwrap()
+                .decrypt_padded_vec_mut::<Pkcs7>(&data)
+                .is_err()
+        );
+        let encrypted_data = STANDARD.encode(data);
+        assert!(decrypt_user_info(SESSION_KEY, &encrypted_data, IV, APP_ID).is_err());
+    }
+
+    #[test]
+    fn rejects_wrong_session_key() {
+        let session_key = STANDARD.encode([0u8; 16]);
+        assert!(decrypt_user_info(&session_key, ENCRYPTED_DATA, IV, APP_ID).is_err());
+    }
+}
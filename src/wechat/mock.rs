@@ -0,0 +1,40 @@
+use crate::util::secret::hash_secret;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use rocket::serde::json::{Json, Value, json};
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![jscode2session]
+}
+
+/// Mimics `jscode2session` so the login flow can run without WeChat: point
+/// `WECHAT_BASE_URL` at the mount point of these routes. The codes
+/// `invalid`, `used`, `limited`, `blocked` and `busy` return the matching
+/// WeChat error; any other code signs in as `mock-<code>`. A code of the form
+/// `<app>:<person>` also returns the unionid `mock-union-<person>`, so
+/// different apps of the same person share it.
+#[get("/sns/jscode2session?<appid>&<secret>&<js_code>&<grant_type>")]
+fn jscode2session(appid: &str, secret: &str, js_code: &str, grant_type: &str) -> Json<Value> {
+    let error = |errcode: i64, errmsg: &str| Json(json!({ "errcode": errcode, "errmsg": errmsg }));
+    if appid.is_empty() || secret.is_empty() || grant_type != "authorization_code" {
+        return error(40013, "invalid appid");
+    }
+    match js_code {
+        "invalid" => error(40029, "invalid code"),
+        "used" => error(40163, "code been used"),
+        "limited" => error(45011, "api minute-quota reach limit"),
+        "blocked" => error(40226, "high risk user"),
+        "busy" => error(-1, "system error"),
+        code => {
+            let session_key = hex::decode(&hash_secret(code)[..32]).unwrap_or_default();
+            let mut body = json!({
+                "openid": format!("mock-{}", code),
+                "session_key": STANDARD.encode(session_key),
+            });
+            if let Some((_, person)) = code.split_once(':') {
+                body["unionid"] = json!(format!("mock-union-{}", person));
+            }
+            Json(body)
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use crate::error::api::ApiError;
+use crate::error::auth::AuthError;
+use crate::model::auth::WxUser;
+use anyhow::anyhow;
+use lazy_static::lazy_static;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use std::env;
+use std::time::Duration;
+
+pub mod crypto;
+pub mod mock;
+
+lazy_static! {
+    pub static ref WECHAT_BASE_URL: String =
+        env::var("WECHAT_BASE_URL").unwrap_or_else(|_| "https://api.weixin.qq.com".to_owned());
+    pub static ref WECHAT_TIMEOUT: u64 = {
+        env::var("WECHAT_TIMEOUT")
+            .unwrap_or_else(|_| "5".to_owned())
+            .parse::<u64>()
+            .unwrap()
+    };
+    pub static ref WECHAT_MOCK: bool = {
+        env::var("WECHAT_MOCK")
+            .unwrap_or_else(|_| "false".to_owned())
+            .parse::<bool>()
+            .unwrap()
+    };
+}
+
+#[derive(Deserialize)]
+struct WxError {
+    errcode: i64,
+    #[serde(default)]
+    errmsg: String,
+}
+
+pub struct WechatClient {
+    client: Client,
+    base_url: String,
+    app_id: Option<String>,
+    app_secret: Option<String>,
+}
+
+impl WechatClient {
+    pub fn new(base_url: &str, app_id: Option<String>, app_secret: Option<String>) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(*WECHAT_TIMEOUT))
+            .connect_timeout(Duration::from_secs(*WECHAT_TIMEOUT))
+            .build()
+            .expect("failed to build WeChat HTTP client");
+        Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            app_id,
+            app_secret,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(
+            WECHAT_BASE_URL.as_str(),
+            env::var("APP_ID").ok(),
+            env::var("APP_SECRET").ok(),
+        )
+    }
+
+    pub fn app_id(&self) -> Result<&str, ApiError> {
+        self.app_id
+            .as_deref()
+            .ok_or_else(|| anyhow!("APP_ID must be set").into())
+    }
+
+    pub async fn code2session(&self, code: &str) -> Result<WxUser, ApiError> {
+        if code.is_empty() {
+            return Err(AuthError::MissingCredentials.into());
+        }
+        let app_secret = self
+            .app_secret
+            .as_deref()
+            .ok_or_else(|| anyhow!("APP_SECRET must be set"))?;
+        let resp = self
+            .client
+            .get(format!("{}/sns/jscode2session", self.base_url))
+            .query(&[
+                ("appid", self.app_id()?),
+                ("secret", app_secret),
+                ("js_code", code),
+                ("grant_type", "authorization_code"),
+            ])
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|err| {
+                warn!("jscode2session request failed: {}", err);
+                AuthError::ProviderUnavailable
+            })?
+            .json::<Value>()
+            .await
+            .map_err(|_| AuthError::ProviderUnavailable)?;
+
+        if let Ok(WxError { errcode, errmsg }) = serde_json::from_value::<WxError>(resp.clone())
+            && errcode != 0
+        {
+            info!("jscode2session errcode: {}, errmsg: {}", errcode, errmsg);
+            return Err(match errcode {
+                40029 => AuthError::InvalidCode,
+                40163 => AuthError::CodeUsed,
+                45011 => AuthError::ProviderRateLimited,
+                40226 => AuthError::UserBlocked,
+                -1 => AuthError::ProviderUnavailable,
+                _ => AuthError::WrongCredentials,
+            }
+            .into());
+        }
+
+        let wx_user =
+            serde_json::from_value::<WxUser>(resp).map_err(|_| AuthError::WrongCredentials)?;
+        if wx_user.openid.is_empty() {
+            Err(AuthError::WrongCredentials.into())
+        } else {
+            Ok(wx_user)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api;
+    use crate::db::BpRecordConn;
+    use crate::sms;
+    use crate::util::jwt::KEYS;
+    use rocket::config::{Config, LogLevel};
+    use rocket::fairing::AdHoc;
+    use rocket::http::{ContentType, Status};
+    use rocket::local::asynchronous::Client as LocalClient;
+    use rocket::tokio::sync::oneshot;
+    use serde_json::json;
+    use std::sync::Mutex;
+    use uuid::Uuid;
+
+    /// Launches the mock on a free port and returns a client pointed at it.
+    async fn mock_client() -> WechatClient {
+        let (sender, receiver) = oneshot::channel();
+        let sender = Mutex::new(Some(sender));
+        let config = Config {
+            port: 0,
+            log_level: LogLevel::Off,
+            ..Config::debug_default()
+        };
+        let rocket = rocket::custom(config)
+            .mount("/", mock::routes())
+            .attach(AdHoc::on_liftoff("Mock Port", move |rocket| {
+                let port = rocket.config().port;
+                if let Some(sender) = sender.lock().unwrap().take() {
+                    let _ = sender.send(port);
+                }
+                Box::pin(async {})
+            }));
+        rocket::tokio::spawn(rocket.launch());
+        let port = receiver.await.unwrap();
+        WechatClient::new(
+            &format!("http://127.0.0.1:{}/", port),
+            Some(String::from("wx-test")),
+            Some(String::from("secret")),
+        )
+    }
+
+    fn status(err: ApiError) -> (Status, AuthError) {
+        match err {
+            ApiError::Auth(err) => err.into(),
+            err => panic!("expected an auth error, got {:?}", err),
+        }
+    }
+
+    #[rocket::async_test]
+    async fn code2session_signs_in() {
+        let client = mock_client().await;
+        let Ok(wx_user) = client.code2session("app1:alice").await else {
+            panic!("mock sign-in failed");
+        };
+        assert_eq!(wx_user.openid, "mock-app1:alice");
+        assert_eq!(wx_user.unionid.as_deref(), Some("mock-union-alice"));
+        assert!(!wx_user.session_key.is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn code2session_maps_errcodes() {
+        let client = mock_client().await;
+        let cases = [
+            ("invalid", Status::Unauthorized),
+            ("used", Status::Unauthorized),
+            ("limited", Status::TooManyRequests),
+            ("blocked", Status::Forbidden),
+            ("busy", Status::ServiceUnavailable),
+        ];
+        for (code, expected) in cases {
+            let Err(err) = client.code2session(code).await else {
+                panic!("code {} signed in", code);
+            };
+            let (status, err) = status(err);
+            assert_eq!(status, expected, "code {}", code);
+            let matched = match code {
+                "invalid" => matches!(err, AuthError::InvalidCode),
+                "used" => matches!(err, AuthError::CodeUsed),
+                "limited" => matches!(err, AuthError::ProviderRateLimited),
+                "blocked" => matches!(err, AuthError::UserBlocked),
+                _ => matches!(err, AuthError::ProviderUnavailable),
+            };
+            assert!(matched, "code {} mapped to {:?}", code, err);
+        }
+    }
+
+    #[rocket::async_test]
+    async fn code2session_reports_unreachable_provider() {
+        let client = WechatClient::new(
+            "http://127.0.0.1:9",
+            Some(String::from("wx-test")),
+            Some(String::from("secret")),
+        );
+        let Err(err) = client.code2session("app1:alice").await else {
+            panic!("signed in without a provider");
+        };
+        let (status, err) = status(err);
+        assert_eq!(status, Status::ServiceUnavailable);
+        assert!(matches!(err, AuthError::ProviderUnavailable));
+    }
+
+    /// Builds the login API with its WeChat client pointed at the mock. Needs
+    /// the database and secrets from the environment or `.env`, migrated.
+    async fn login_api() -> LocalClient {
+        dotenvy::dotenv().ok();
+        let rocket = rocket::build()
+            .attach(BpRecordConn::fairing())
+            .manage(sms::from_env())
+            .manage(mock_client().await)
+            .mount("/api", api::routes());
+        LocalClient::untracked(rocket).await.unwrap()
+    }
+
+    async fn login(api: &LocalClient, code: &str) -> (Status, String) {
+        let response = api
+            .post("/api/login")
+            .header(ContentType::JSON)
+            .body(json!({ "code": code }).to_string())
+            .dispatch()
+            .await;
+        (
+            response.status(),
+            response.into_string().await.unwrap_or_default(),
+        )
+    }
+
+    #[rocket::async_test]
+    #[ignore = "needs the database configured in .env"]
+    async fn login_route_signs_in_through_mock() {
+        let api = login_api().await;
+        let person = Uuid::new_v4();
+        let mut subjects = Vec::new();
+        for code in [
+            format!("app1:{}", person),
+            format!("app1:{}", person),
+            format!("app2:{}", person),
+        ] {
+            let (status, body) = login(&api, &code).await;
+            assert_eq!(status, Status::Ok, "code {}: {}", code, body);
+            let body = serde_json::from_str::<Value>(&body).unwrap();
+            assert!(body["refresh_token"].is_string());
+            let token = body["access_token"].as_str().unwrap();
+            subjects.push(KEYS.decode(token).unwrap().claims.sub);
+        }
+        // The same openid, then another app's openid with the same unionid.
+        assert_eq!(subjects[0], subjects[1]);
+        assert_eq!(subjects[0], subjects[2]);
+    }
+
+    #[rocket::async_test]
+    #[ignore = "needs the database configured in .env"]
+    async fn login_route_maps_errcodes() {
+        let api = login_api().await;
+        let cases = [
+            ("invalid", Status::Unauthorized, "Invalid login code"),
+            ("used", Status::Unauthorized, "Login code already used"),
+            (
+                "limited",
+                Status::TooManyRequests,
+                "Identity provider rate limited",
+            ),
+            (
+                "blocked",
+                Status::Forbidden,
+                "User blocked by identity provider",
+            ),
+            (
+                "busy",
+                Status::ServiceUnavailable,
+                "Identity provider unavailable",
+            ),
+        ];
+        for (code, status, message) in cases {
+            assert_eq!(
+                login(&api, code).await,
+                (status, String::from(message)),
+                "code {}",
+                code
+            );
+        }
+    }
+}